@@ -0,0 +1,7 @@
+-- Per-session facts needed to evaluate achievement requirements
+
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS cameras_used BOOLEAN;
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS power_out BOOLEAN NOT NULL DEFAULT false;
+
+-- Discrete one-off events seen during the session, e.g. "golden_freddy_seen", "minigame:ball_pit"
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS events JSONB NOT NULL DEFAULT '[]'::jsonb;
@@ -0,0 +1,8 @@
+-- Per-session facts needed to validate daily challenge completions
+
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS starting_power INTEGER NOT NULL DEFAULT 100;
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS left_door_used BOOLEAN;
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS right_door_used BOOLEAN;
+
+-- A completion must point at the session that earned it
+ALTER TABLE daily_challenge_completions ADD COLUMN IF NOT EXISTS session_id UUID REFERENCES game_sessions(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Guests authenticate their WebSocket with a token issued by join_room
+ALTER TABLE multiplayer_participants ADD COLUMN IF NOT EXISTS guest_token_hash VARCHAR(64);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_participants_guest_token ON multiplayer_participants(guest_token_hash);
@@ -0,0 +1,7 @@
+-- Input logs for sessions whose result was verified by re-running the night on the server
+CREATE TABLE IF NOT EXISTS session_replays (
+    session_id UUID PRIMARY KEY REFERENCES game_sessions(id) ON DELETE CASCADE,
+    seed BIGINT NOT NULL,
+    inputs JSONB NOT NULL DEFAULT '[]'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
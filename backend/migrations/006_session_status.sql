@@ -0,0 +1,8 @@
+-- Sessions move through started -> in_progress -> finished, or are abandoned
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'started';
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS last_heartbeat_at TIMESTAMPTZ;
+
+-- Sessions that ended before states existed
+UPDATE game_sessions SET status = 'finished' WHERE ended_at IS NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_game_sessions_open ON game_sessions(status) WHERE status IN ('started', 'in_progress');
@@ -0,0 +1,4 @@
+-- Sessions whose reported result failed the plausibility checks are kept for review
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS flagged_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_game_sessions_rejected ON game_sessions(ended_at DESC) WHERE status = 'rejected';
@@ -0,0 +1,8 @@
+-- Slices are recorded individually, the profile count is derived from them
+UPDATE pizza_slice_progress SET found_at = NOW() WHERE found_at IS NULL;
+ALTER TABLE pizza_slice_progress ALTER COLUMN found_at SET NOT NULL;
+
+UPDATE player_profiles p
+SET pizza_slices_collected = (
+    SELECT COUNT(DISTINCT s.slice_id) FROM pizza_slice_progress s WHERE s.user_id = p.user_id
+);
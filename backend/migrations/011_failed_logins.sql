@@ -0,0 +1,12 @@
+-- Audit trail of failed login attempts
+CREATE TABLE IF NOT EXISTS failed_logins (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL, -- set when the account exists
+    ip_address VARCHAR(45),
+    reason VARCHAR(50) NOT NULL, -- 'unknown_account', 'wrong_password'
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_logins_email ON failed_logins(email, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_failed_logins_ip ON failed_logins(ip_address, created_at DESC);
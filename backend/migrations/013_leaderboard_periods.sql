@@ -0,0 +1,26 @@
+-- Per-period leaderboards: every score is kept as the best of its day, week and month as
+-- well as all time. period_start is the UTC date the period begins on, weeks start on Monday
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS period VARCHAR(10) NOT NULL DEFAULT 'all'; -- 'daily', 'weekly', 'monthly', 'all'
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS period_start DATE NOT NULL DEFAULT '1970-01-01';
+
+ALTER TABLE leaderboard_entries DROP CONSTRAINT IF EXISTS leaderboard_entries_user_id_leaderboard_type_key;
+ALTER TABLE leaderboard_entries DROP CONSTRAINT IF EXISTS leaderboard_entries_period_key;
+ALTER TABLE leaderboard_entries
+    ADD CONSTRAINT leaderboard_entries_period_key UNIQUE (user_id, leaderboard_type, period, period_start);
+
+DROP INDEX IF EXISTS idx_leaderboard_type_score;
+CREATE INDEX IF NOT EXISTS idx_leaderboard_period_score
+    ON leaderboard_entries(leaderboard_type, period, period_start, score DESC);
+
+-- Existing all-time bests also count for the periods they were achieved in
+INSERT INTO leaderboard_entries (user_id, username, leaderboard_type, score, additional_data, achieved_at, period, period_start)
+SELECT e.user_id, e.username, e.leaderboard_type, e.score, e.additional_data, e.achieved_at, p.period,
+       CASE p.period
+           WHEN 'daily' THEN (e.achieved_at AT TIME ZONE 'UTC')::date
+           WHEN 'weekly' THEN date_trunc('week', e.achieved_at AT TIME ZONE 'UTC')::date
+           ELSE date_trunc('month', e.achieved_at AT TIME ZONE 'UTC')::date
+       END
+FROM leaderboard_entries e
+CROSS JOIN (VALUES ('daily'), ('weekly'), ('monthly')) AS p(period)
+WHERE e.period = 'all' AND e.achieved_at IS NOT NULL
+ON CONFLICT (user_id, leaderboard_type, period, period_start) DO NOTHING;
@@ -0,0 +1,20 @@
+-- Friendships: a pending request until the addressee accepts it, declined and removed
+-- friendships are deleted so the request can be sent again
+CREATE TABLE IF NOT EXISTS friendships (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    requester_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    addressee_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- 'pending', 'accepted'
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    accepted_at TIMESTAMPTZ,
+    CHECK (requester_id <> addressee_id)
+);
+
+-- One friendship per pair, whichever way round it was requested
+CREATE UNIQUE INDEX IF NOT EXISTS idx_friendships_pair
+    ON friendships(LEAST(requester_id, addressee_id), GREATEST(requester_id, addressee_id));
+CREATE INDEX IF NOT EXISTS idx_friendships_addressee ON friendships(addressee_id, status);
+CREATE INDEX IF NOT EXISTS idx_friendships_requester ON friendships(requester_id, status);
+
+-- Presence for the friends list, touched at most once a minute by authenticated requests
+ALTER TABLE users ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ;
@@ -0,0 +1,21 @@
+-- Link leaderboard entries to the session that produced them, and keep the run time for
+-- tie-breaking: equal scores rank by earlier achieved_at, then shorter time
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS session_id UUID REFERENCES game_sessions(id) ON DELETE SET NULL;
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS time_seconds INTEGER;
+
+UPDATE leaderboard_entries e
+SET session_id = s.id,
+    time_seconds = s.time_survived_seconds
+FROM game_sessions s
+WHERE e.session_id IS NULL
+  AND s.id::text = e.additional_data->>'session_id';
+
+-- achieved_at is part of the ranking now
+UPDATE leaderboard_entries SET achieved_at = NOW() WHERE achieved_at IS NULL;
+ALTER TABLE leaderboard_entries ALTER COLUMN achieved_at SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_leaderboard_entries_session ON leaderboard_entries(session_id);
+
+DROP INDEX IF EXISTS idx_leaderboard_period_score;
+CREATE INDEX IF NOT EXISTS idx_leaderboard_period_rank
+    ON leaderboard_entries(leaderboard_type, period, period_start, score DESC, achieved_at, time_seconds);
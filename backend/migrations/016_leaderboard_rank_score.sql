@@ -0,0 +1,54 @@
+-- Boards can rank ascending (speed_run) or descending. rank_score is the score mapped so
+-- that higher is always better, which keeps every ranking query on one descending index
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS rank_score BIGINT;
+
+UPDATE leaderboard_entries
+SET rank_score = CASE WHEN leaderboard_type = 'speed_run' THEN -score ELSE score END
+WHERE rank_score IS NULL;
+
+ALTER TABLE leaderboard_entries ALTER COLUMN rank_score SET NOT NULL;
+
+-- speed_run kept each player's slowest time so far, rebuild it from their fastest runs
+DELETE FROM leaderboard_entries WHERE leaderboard_type = 'speed_run';
+
+INSERT INTO leaderboard_entries
+    (user_id, username, leaderboard_type, score, rank_score, additional_data, achieved_at,
+     period, period_start, session_id, time_seconds)
+SELECT DISTINCT ON (s.user_id, p.period, period_start)
+    s.user_id,
+    u.username,
+    'speed_run',
+    s.time_survived_seconds,
+    -s.time_survived_seconds,
+    jsonb_build_object(
+        'session_id', s.id,
+        'night', s.night_number,
+        'final_power', s.final_power,
+        'time_seconds', s.time_survived_seconds,
+        'star_rating', s.star_rating,
+        'easy_mode', s.easy_mode
+    ),
+    s.ended_at,
+    p.period,
+    CASE p.period
+        WHEN 'daily' THEN (s.ended_at AT TIME ZONE 'UTC')::date
+        WHEN 'weekly' THEN date_trunc('week', s.ended_at AT TIME ZONE 'UTC')::date
+        WHEN 'monthly' THEN date_trunc('month', s.ended_at AT TIME ZONE 'UTC')::date
+        ELSE DATE '1970-01-01'
+    END AS period_start,
+    s.id,
+    s.time_survived_seconds
+FROM game_sessions s
+JOIN users u ON u.id = s.user_id
+CROSS JOIN (VALUES ('daily'), ('weekly'), ('monthly'), ('all')) AS p(period)
+WHERE s.session_type = 'night'
+  AND s.status = 'finished'
+  AND s.survived = true
+  AND s.easy_mode = false
+  AND s.time_survived_seconds IS NOT NULL
+  AND s.ended_at IS NOT NULL
+ORDER BY s.user_id, p.period, period_start, s.time_survived_seconds ASC, s.ended_at ASC;
+
+DROP INDEX IF EXISTS idx_leaderboard_period_rank;
+CREATE INDEX IF NOT EXISTS idx_leaderboard_period_rank
+    ON leaderboard_entries(leaderboard_type, period, period_start, rank_score DESC, achieved_at, time_seconds);
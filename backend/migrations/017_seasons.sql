@@ -0,0 +1,57 @@
+-- Competitive seasons: leaderboard entries belong to a season, and when a season ends its
+-- final all-time standings are frozen into season_standings and rewards are handed out
+CREATE TABLE IF NOT EXISTS seasons (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    number INTEGER NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL,
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    reward_skin VARCHAR(50), -- added to unlocked_skins of every player placing in the top of a board
+    status VARCHAR(20) NOT NULL DEFAULT 'active', -- 'active', 'archived'
+    archived_at TIMESTAMPTZ,
+    CHECK (ends_at > starts_at)
+);
+
+-- Only one season runs at a time
+CREATE UNIQUE INDEX IF NOT EXISTS idx_seasons_active ON seasons(status) WHERE status = 'active';
+
+-- The first season holds everything recorded so far
+INSERT INTO seasons (number, name, starts_at, ends_at, reward_skin)
+SELECT 1,
+       'Season 1',
+       COALESCE((SELECT MIN(achieved_at) FROM leaderboard_entries), NOW()),
+       GREATEST(COALESCE((SELECT MIN(achieved_at) FROM leaderboard_entries), NOW()), NOW()) + INTERVAL '91 days',
+       'season_1_medal'
+WHERE NOT EXISTS (SELECT 1 FROM seasons);
+
+ALTER TABLE leaderboard_entries ADD COLUMN IF NOT EXISTS season_id UUID REFERENCES seasons(id) ON DELETE CASCADE;
+UPDATE leaderboard_entries SET season_id = (SELECT id FROM seasons WHERE number = 1) WHERE season_id IS NULL;
+ALTER TABLE leaderboard_entries ALTER COLUMN season_id SET NOT NULL;
+
+ALTER TABLE leaderboard_entries DROP CONSTRAINT IF EXISTS leaderboard_entries_period_key;
+ALTER TABLE leaderboard_entries DROP CONSTRAINT IF EXISTS leaderboard_entries_season_period_key;
+ALTER TABLE leaderboard_entries
+    ADD CONSTRAINT leaderboard_entries_season_period_key
+    UNIQUE (season_id, user_id, leaderboard_type, period, period_start);
+
+DROP INDEX IF EXISTS idx_leaderboard_period_rank;
+CREATE INDEX IF NOT EXISTS idx_leaderboard_season_rank
+    ON leaderboard_entries(season_id, leaderboard_type, period, period_start, rank_score DESC, achieved_at, time_seconds);
+
+-- Final standings of archived seasons, one row per player per board
+CREATE TABLE IF NOT EXISTS season_standings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    season_id UUID NOT NULL REFERENCES seasons(id) ON DELETE CASCADE,
+    leaderboard_type VARCHAR(50) NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    username VARCHAR(50) NOT NULL,
+    final_rank BIGINT NOT NULL,
+    score BIGINT NOT NULL,
+    achieved_at TIMESTAMPTZ NOT NULL,
+    session_id UUID REFERENCES game_sessions(id) ON DELETE SET NULL,
+    reward_skin VARCHAR(50),
+    UNIQUE(season_id, leaderboard_type, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_season_standings_board ON season_standings(season_id, leaderboard_type, final_rank);
+CREATE INDEX IF NOT EXISTS idx_season_standings_user ON season_standings(user_id);
@@ -0,0 +1,6 @@
+-- Scoring rules are versioned, see src/scoring. Sessions scored before this were scored
+-- with version 1
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS scoring_version INTEGER;
+
+UPDATE game_sessions SET scoring_version = 1
+WHERE scoring_version IS NULL AND status = 'finished' AND survived IS NOT NULL;
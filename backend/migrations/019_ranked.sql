@@ -0,0 +1,33 @@
+-- Ranked versus: a rating per user and role, and the matches the matchmaking queue made
+CREATE TABLE IF NOT EXISTS player_ratings (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL, -- 'guard', 'animatronic'
+    rating INTEGER NOT NULL DEFAULT 1500,
+    games_played INTEGER NOT NULL DEFAULT 0,
+    wins INTEGER NOT NULL DEFAULT 0,
+    losses INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS idx_player_ratings_role ON player_ratings(role, rating DESC);
+
+-- Matched rooms are ranked, roles are assigned by the queue
+ALTER TABLE multiplayer_rooms ADD COLUMN IF NOT EXISTS ranked BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS ranked_matches (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    room_id UUID NOT NULL UNIQUE REFERENCES multiplayer_rooms(id) ON DELETE CASCADE,
+    guard_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    animatronic_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    guard_rating INTEGER NOT NULL, -- ratings when the match was made
+    animatronic_rating INTEGER NOT NULL,
+    winner_role VARCHAR(20), -- NULL until the result is recorded, stays NULL for abandoned matches
+    guard_rating_change INTEGER,
+    animatronic_rating_change INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_ranked_matches_guard ON ranked_matches(guard_user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_ranked_matches_animatronic ON ranked_matches(animatronic_user_id, created_at DESC);
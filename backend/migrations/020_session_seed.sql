@@ -0,0 +1,3 @@
+-- The server picks the seed a night is simulated with, so replays can't choose a favourable one.
+-- Sessions started before this have none and can't be verified
+ALTER TABLE game_sessions ADD COLUMN IF NOT EXISTS seed BIGINT;
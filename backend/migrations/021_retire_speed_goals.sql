@@ -0,0 +1,5 @@
+-- Night times come from the simulation, which always runs the full six hours, so
+-- "survive a night in under 300 seconds" can't be met. Speed Demon asks for a hard night
+-- without the cameras instead
+UPDATE achievements SET requirements = '{"night": 5, "no_cameras": true}'
+WHERE id = 'speed_demon' AND requirements = '{"time_under": 300}';
@@ -5,8 +5,14 @@ pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
     pub jwt_expiry_hours: i64,
+    pub refresh_token_expiry_days: i64,
     pub host: String,
     pub port: u16,
+    pub app_url: String, // where links in emails point to
+    pub smtp_url: Option<String>, // emails are only logged when unset
+    pub mail_from: String,
+    pub mail_dir: Option<String>, // write logged emails to this directory
+    pub require_verified_email: bool,
 }
 
 impl Config {
@@ -20,11 +26,23 @@ impl Config {
                 .unwrap_or_else(|_| "24".into())
                 .parse()
                 .unwrap_or(24),
+            refresh_token_expiry_days: std::env::var("REFRESH_TOKEN_EXPIRY_DAYS")
+                .unwrap_or_else(|_| "30".into())
+                .parse()
+                .unwrap_or(30),
             host: std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".into()),
             port: std::env::var("PORT")
                 .unwrap_or_else(|_| "3000".into())
                 .parse()
                 .unwrap_or(3000),
+            app_url: std::env::var("APP_URL").unwrap_or_else(|_| "http://localhost:5173".into()),
+            smtp_url: std::env::var("SMTP_URL").ok(),
+            mail_from: std::env::var("MAIL_FROM")
+                .unwrap_or_else(|_| "FNAF <noreply@localhost>".into()),
+            mail_dir: std::env::var("MAIL_DIR").ok(),
+            require_verified_email: std::env::var("REQUIRE_VERIFIED_EMAIL")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(false),
         })
     }
 }
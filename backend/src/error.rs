@@ -5,6 +5,8 @@ use axum::{
 };
 use serde_json::json;
 
+use crate::validation::FieldErrors;
+
 #[derive(Debug)]
 pub enum AppError {
     NotFound(String),
@@ -14,6 +16,7 @@ pub enum AppError {
     Conflict(String),
     Internal(String),
     Database(sqlx::Error),
+    Validation(FieldErrors),
 }
 
 impl IntoResponse for AppError {
@@ -29,6 +32,13 @@ impl IntoResponse for AppError {
                 tracing::error!("Database error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string())
             }
+            AppError::Validation(fields) => {
+                let body = Json(json!({
+                    "error": "Validation failed",
+                    "fields": fields
+                }));
+                return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+            }
         };
 
         let body = Json(json!({
@@ -1,8 +1,12 @@
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod matchmaking;
 pub mod middleware;
 pub mod models;
 pub mod routes;
+pub mod scoring;
 pub mod services;
+pub mod simulation;
+pub mod validation;
 pub mod websocket;
@@ -1,9 +1,9 @@
 use axum::{
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use sqlx::postgres::PgPoolOptions;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -11,19 +11,26 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 mod config;
 mod db;
 mod error;
+mod matchmaking;
 mod middleware;
 mod models;
 mod routes;
+mod scoring;
 mod services;
+mod simulation;
+mod validation;
 mod websocket;
 
 use config::Config;
-use routes::{auth, challenges, leaderboard, multiplayer, achievements, users};
+use middleware::LoginRateLimitLayer;
+use services::{LogMailer, Mailer, SmtpMailer};
+use routes::{admin, auth, challenges, friends, leaderboard, multiplayer, achievements, pizza, users, seasons};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: sqlx::PgPool,
     pub config: Arc<Config>,
+    pub mailer: Arc<dyn Mailer>,
 }
 
 #[tokio::main]
@@ -49,31 +56,88 @@ async fn main() -> anyhow::Result<()> {
     // Run migrations
     sqlx::migrate!("./migrations").run(&db).await?;
 
+    // Refuse to start with achievements we cannot evaluate
+    services::AchievementService::validate_catalog(&db).await?;
+
+    // Versus games cut off by a restart can not resume without their simulation
+    let interrupted = websocket::finish_interrupted_rooms(&db).await?;
+    if interrupted > 0 {
+        tracing::info!("Finished {} interrupted versus rooms", interrupted);
+    }
+
+    // Sessions left open past the timeout are abandoned in the background
+    services::SessionService::spawn_abandon_task(db.clone());
+
+    // Ended seasons are archived and the next one started in the background
+    services::SeasonService::spawn_rollover_task(db.clone());
+
+    // Queued ranked players are paired in the background
+    services::MatchmakingService::spawn_matchmaking_task(db.clone());
+
+    // Emails are only logged unless SMTP is configured
+    let mailer: Arc<dyn Mailer> = match &config.smtp_url {
+        Some(url) => Arc::new(SmtpMailer::new(url, &config.mail_from)?),
+        None => Arc::new(LogMailer::new(config.mail_dir.clone().map(Into::into))),
+    };
+
     let state = AppState {
         db,
         config: Arc::new(config.clone()),
+        mailer,
     };
 
     // Build router
     let app = Router::new()
         // Auth routes
         .route("/api/auth/register", post(auth::register))
-        .route("/api/auth/login", post(auth::login))
+        .route(
+            "/api/auth/login",
+            post(auth::login).layer(LoginRateLimitLayer::default()),
+        )
         .route("/api/auth/refresh", post(auth::refresh))
+        .route("/api/auth/logout", post(auth::logout))
+        .route("/api/auth/logout-all", post(auth::logout_all))
         .route("/api/auth/me", get(auth::me))
+        .route("/api/auth/verify-email", post(auth::verify_email))
+        .route("/api/auth/verify-email/request", post(auth::request_verification))
+        .route("/api/auth/password-reset", post(auth::reset_password))
+        .route("/api/auth/password-reset/request", post(auth::request_password_reset))
         // User/Profile routes
         .route("/api/profile", get(users::get_profile).put(users::update_profile))
         .route("/api/profile/:id", get(users::get_public_profile))
         // Game sessions
         .route("/api/sessions", post(users::create_session))
         .route("/api/sessions/:id", put(users::update_session))
+        .route("/api/sessions/:id/heartbeat", post(users::session_heartbeat))
+        .route("/api/sessions/:id/replay", get(users::get_replay))
         // Achievements
         .route("/api/achievements", get(achievements::list_all))
         .route("/api/achievements/mine", get(achievements::get_mine))
         .route("/api/achievements/:id/claim", post(achievements::claim))
+        // Pizza slices
+        .route("/api/pizza/slices", get(pizza::get_catalog))
+        .route("/api/pizza/slices/mine", get(pizza::get_mine).post(pizza::collect))
+        // Friends
+        .route("/api/friends", get(friends::list_friends))
+        .route("/api/friends/:user_id", delete(friends::remove_friend))
+        .route("/api/friends/requests", get(friends::list_requests).post(friends::send_request))
+        .route("/api/friends/requests/:id/accept", post(friends::accept_request))
+        .route("/api/friends/requests/:id/decline", post(friends::decline_request))
         // Leaderboards
+        .route("/api/leaderboard", get(leaderboard::list_leaderboards))
+        .route("/api/leaderboard/entries/:id", get(leaderboard::get_entry))
         .route("/api/leaderboard/:type", get(leaderboard::get_leaderboard))
         .route("/api/leaderboard/:type/rank", get(leaderboard::get_my_rank))
+        .route("/api/leaderboard/:type/around-me", get(leaderboard::get_around_me))
+        .route("/api/leaderboard/:type/periods", get(leaderboard::get_periods))
+        // Scoring rules
+        .route("/api/scoring/rules", get(routes::scoring::get_rules))
+        .route("/api/scoring/rules/:version", get(routes::scoring::get_rules_version))
+        // Seasons
+        .route("/api/seasons", get(seasons::list_seasons))
+        .route("/api/seasons/current", get(seasons::get_current))
+        .route("/api/seasons/mine", get(seasons::get_my_placements))
+        .route("/api/seasons/:id/standings/:type", get(seasons::get_standings))
         // Daily challenges
         .route("/api/challenges/today", get(challenges::get_today))
         .route("/api/challenges/history", get(challenges::get_history))
@@ -82,8 +146,26 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/multiplayer/rooms", post(multiplayer::create_room))
         .route("/api/multiplayer/rooms/:code", get(multiplayer::get_room))
         .route("/api/multiplayer/rooms/:code/join", post(multiplayer::join_room))
+        // Ranked matchmaking
+        .route(
+            "/api/matchmaking/queue",
+            get(routes::matchmaking::get_queue_status)
+                .post(routes::matchmaking::join_queue)
+                .delete(routes::matchmaking::leave_queue),
+        )
+        .route("/api/matchmaking/ratings", get(routes::matchmaking::get_my_ratings))
+        // Admin
+        .route("/api/admin/users/:id/deactivate", post(admin::deactivate_user))
+        .route("/api/admin/users/:id/reactivate", post(admin::reactivate_user))
+        .route("/api/admin/users/:id/role", put(admin::set_role))
+        .route("/api/admin/users/:id/leaderboard/wipe", post(admin::wipe_leaderboard))
+        .route("/api/admin/sessions/flagged", get(admin::list_flagged_sessions))
+        .route("/api/admin/sessions/:id/void", post(admin::void_session))
+        .route("/api/admin/audit-log", get(admin::get_audit_log))
+        .route("/api/admin/scoring/rescore", post(admin::rescore))
         // WebSocket for multiplayer
         .route("/ws/game/:room_code", get(websocket::game_ws_handler))
+        .route("/ws/matchmaking", get(websocket::matchmaking::matchmaking_ws_handler))
         // Middleware
         .layer(TraceLayer::new_for_http())
         .layer(
@@ -99,7 +181,8 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Starting server on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    // Client addresses are needed for per-IP login limits
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
@@ -0,0 +1,161 @@
+// Matchmaking queue for ranked versus
+// Waiting players are kept in memory, `MatchmakingService` pairs them on a fixed interval
+// and creates a ranked room for every pair
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::models::*;
+
+/// Rating difference accepted right after joining the queue
+pub const BASE_SEARCH_WINDOW: i32 = 50;
+/// The window widens by `SEARCH_WINDOW_STEP` for every `WIDEN_EVERY_SECONDS` spent waiting
+pub const SEARCH_WINDOW_STEP: i32 = 25;
+pub const WIDEN_EVERY_SECONDS: i64 = 10;
+pub const MAX_SEARCH_WINDOW: i32 = 500;
+
+lazy_static::lazy_static! {
+    pub static ref MATCH_QUEUE: Mutex<Vec<QueuedPlayer>> = Mutex::new(Vec::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedPlayer {
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String, // 'guard', 'animatronic' or 'any'
+    pub guard_rating: i32,
+    pub animatronic_rating: i32,
+    pub queued_at: DateTime<Utc>,
+    pub notify: Option<mpsc::UnboundedSender<String>>, // the matchmaking socket, if queued through one
+}
+
+impl QueuedPlayer {
+    pub fn rating(&self, role: &str) -> i32 {
+        if role == ROOM_ROLE_GUARD {
+            self.guard_rating
+        } else {
+            self.animatronic_rating
+        }
+    }
+
+    pub fn search_window(&self, now: DateTime<Utc>) -> i32 {
+        let waited = (now - self.queued_at).num_seconds().max(0);
+        (BASE_SEARCH_WINDOW + (waited / WIDEN_EVERY_SECONDS) as i32 * SEARCH_WINDOW_STEP)
+            .min(MAX_SEARCH_WINDOW)
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> QueueStatusResponse {
+        QueueStatusResponse {
+            status: QUEUE_QUEUED.to_string(),
+            role: Some(self.role.clone()),
+            queued_at: Some(self.queued_at),
+            search_window: Some(self.search_window(now)),
+            matched: None,
+        }
+    }
+
+    fn plays(&self, role: &str) -> bool {
+        self.role == QUEUE_ROLE_ANY || self.role == role
+    }
+}
+
+/// Two players the queue settled on
+#[derive(Debug)]
+pub struct Pairing {
+    pub guard: QueuedPlayer,
+    pub animatronic: QueuedPlayer,
+}
+
+/// Put a player in the queue, replacing any earlier entry of theirs
+pub fn enqueue(player: QueuedPlayer) {
+    let mut queue = MATCH_QUEUE.lock().unwrap();
+    queue.retain(|p| p.user_id != player.user_id);
+    queue.push(player);
+}
+
+/// Remove a player from the queue, returns whether they were in it
+pub fn dequeue(user_id: Uuid) -> bool {
+    let mut queue = MATCH_QUEUE.lock().unwrap();
+    let before = queue.len();
+    queue.retain(|p| p.user_id != user_id);
+    queue.len() != before
+}
+
+/// Remove a player's entry only if it was queued with this notification channel
+pub fn dequeue_notified_by(user_id: Uuid, notify: &mpsc::UnboundedSender<String>) {
+    let mut queue = MATCH_QUEUE.lock().unwrap();
+    queue.retain(|p| {
+        p.user_id != user_id || !p.notify.as_ref().map_or(false, |n| n.same_channel(notify))
+    });
+}
+
+pub fn queued_status(user_id: Uuid, now: DateTime<Utc>) -> Option<QueueStatusResponse> {
+    MATCH_QUEUE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|p| p.user_id == user_id)
+        .map(|p| p.status(now))
+}
+
+/// Take every pair that can be made out of the queue, longest waiting players first.
+/// A pair is made when the rating gap is within both players' search windows,
+/// and each player gets the closest acceptable opponent
+pub fn take_pairings(now: DateTime<Utc>) -> Vec<Pairing> {
+    let mut queue = MATCH_QUEUE.lock().unwrap();
+    queue.sort_by_key(|p| p.queued_at);
+
+    let mut pairings = Vec::new();
+    let mut i = 0;
+    while i < queue.len() {
+        let player = &queue[i];
+        let window = player.search_window(now);
+
+        let best = queue
+            .iter()
+            .enumerate()
+            .skip(i + 1)
+            .filter_map(|(j, other)| {
+                let (player_guards, gap) = assign_roles(player, other)?;
+                (gap <= window.min(other.search_window(now))).then_some((j, player_guards, gap))
+            })
+            .min_by_key(|&(_, _, gap)| gap);
+
+        match best {
+            Some((j, player_guards, _)) => {
+                // j > i, so removing it first keeps i valid
+                let other = queue.remove(j);
+                let player = queue.remove(i);
+                let (guard, animatronic) = if player_guards { (player, other) } else { (other, player) };
+                pairings.push(Pairing { guard, animatronic });
+            }
+            None => i += 1,
+        }
+    }
+
+    pairings
+}
+
+/// Whether `a` should guard against `b`, with the rating gap that gives.
+/// Players who queued for either side are placed where the gap is smallest
+fn assign_roles(a: &QueuedPlayer, b: &QueuedPlayer) -> Option<(bool, i32)> {
+    [true, false]
+        .into_iter()
+        .filter(|&a_guards| {
+            if a_guards {
+                a.plays(ROOM_ROLE_GUARD) && b.plays(ROOM_ROLE_ANIMATRONIC)
+            } else {
+                a.plays(ROOM_ROLE_ANIMATRONIC) && b.plays(ROOM_ROLE_GUARD)
+            }
+        })
+        .map(|a_guards| {
+            let gap = if a_guards {
+                a.guard_rating - b.animatronic_rating
+            } else {
+                a.animatronic_rating - b.guard_rating
+            };
+            (a_guards, gap.abs())
+        })
+        .min_by_key(|&(_, gap)| gap)
+}
@@ -4,7 +4,12 @@ use axum::{
     http::{header::AUTHORIZATION, request::Parts, StatusCode},
 };
 
-use crate::{routes::auth::{decode_token, Claims}, AppState};
+use crate::{
+    models::{ROLE_ADMIN, ROLE_MODERATOR},
+    routes::auth::{decode_token, is_token_revoked, Claims},
+    services::FriendService,
+    AppState,
+};
 
 #[async_trait]
 impl FromRequestParts<AppState> for Claims {
@@ -27,8 +32,26 @@ impl FromRequestParts<AppState> for Claims {
             .ok_or((StatusCode::UNAUTHORIZED, "Invalid authorization header format"))?;
 
         // Decode token
-        decode_token(token, &state.config.jwt_secret)
-            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token"))
+        let claims = decode_token(token, &state.config.jwt_secret)
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token"))?;
+
+        // Reject tokens whose login has been logged out
+        match is_token_revoked(&state.db, claims.jti).await {
+            Ok(false) => {}
+            Ok(true) => return Err((StatusCode::UNAUTHORIZED, "Token has been revoked")),
+            Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error")),
+        }
+
+        // Presence for the friends list, the request doesn't wait for it
+        let db = state.db.clone();
+        let user_id = claims.sub;
+        tokio::spawn(async move {
+            if let Err(e) = FriendService::touch_last_seen(&db, user_id).await {
+                tracing::warn!("Failed to update last seen for {}: {:?}", user_id, e);
+            }
+        });
+
+        Ok(claims)
     }
 }
 
@@ -47,3 +70,60 @@ impl FromRequestParts<AppState> for OptionalClaims {
         Ok(OptionalClaims(claims))
     }
 }
+
+// Staff extractor (moderators and admins), the role is read from the database on every request
+pub struct Staff {
+    pub claims: Claims,
+    pub role: String,
+}
+
+impl Staff {
+    pub fn is_admin(&self) -> bool {
+        self.role == ROLE_ADMIN
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Staff {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        let role = sqlx::query_scalar!(
+            "SELECT role FROM users WHERE id = $1 AND is_active = true",
+            claims.sub
+        )
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Database error"))?;
+
+        match role {
+            Some(role) if role == ROLE_MODERATOR || role == ROLE_ADMIN => Ok(Staff { claims, role }),
+            _ => Err((StatusCode::FORBIDDEN, "Moderator access required")),
+        }
+    }
+}
+
+// Admin extractor, for actions moderators can't take
+pub struct Admin(pub Claims);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let staff = Staff::from_request_parts(parts, state).await?;
+        if !staff.is_admin() {
+            return Err((StatusCode::FORBIDDEN, "Admin access required"));
+        }
+
+        Ok(Admin(staff.claims))
+    }
+}
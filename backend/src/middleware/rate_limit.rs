@@ -0,0 +1,511 @@
+// Login rate limiting
+// Failed logins are counted per client IP and per account; after a few failures each
+// further attempt has to wait exponentially longer, until the key is locked out
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower::{Layer, Service};
+
+/// Largest login body the limiter will buffer to find the account
+const MAX_BODY_BYTES: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLimits {
+    pub free_failures: u32, // failures allowed before any backoff
+    pub lockout_after: u32, // failures before the key is locked out
+    pub base_backoff: Duration,
+    pub lockout: Duration,
+    pub reset_after: Duration, // failures are forgotten after this long without one
+}
+
+impl LoginLimits {
+    pub fn per_account() -> Self {
+        LoginLimits {
+            free_failures: 2,
+            lockout_after: 5,
+            base_backoff: Duration::from_secs(1),
+            lockout: Duration::from_secs(15 * 60),
+            reset_after: Duration::from_secs(15 * 60),
+        }
+    }
+
+    /// Looser than per account, many players can share an IP
+    pub fn per_ip() -> Self {
+        LoginLimits {
+            free_failures: 10,
+            lockout_after: 50,
+            ..Self::per_account()
+        }
+    }
+
+    /// How long a key has to wait after its `failures`th failure
+    pub fn backoff(&self, failures: u32) -> Duration {
+        if failures >= self.lockout_after {
+            self.lockout
+        } else if failures > self.free_failures {
+            let exponent = (failures - self.free_failures - 1).min(31);
+            self.base_backoff
+                .saturating_mul(1 << exponent)
+                .min(self.lockout)
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttemptState {
+    pub failures: u32,
+    pub last_failure: Option<Instant>,
+    pub blocked_until: Option<Instant>,
+    pub in_flight: u32, // reserved attempts still waiting for their response
+}
+
+impl AttemptState {
+    /// Failures that still count against the key at `now`
+    fn recent_failures(&self, limits: &LoginLimits, now: Instant) -> u32 {
+        match self.last_failure {
+            Some(last) if now.duration_since(last) < limits.reset_after => self.failures,
+            _ => 0,
+        }
+    }
+}
+
+/// Where failed attempts are counted, in memory by default
+pub trait AttemptStore: Send + Sync {
+    /// Replace the state of `key` with what `f` returns, `None` removes it.
+    /// Must be atomic, concurrent logins for the same key race through here
+    fn update(&self, key: &str, f: &mut dyn FnMut(Option<AttemptState>) -> Option<AttemptState>);
+    /// Drop entries that no longer block anything
+    fn prune(&self, now: Instant, reset_after: Duration);
+}
+
+#[derive(Default)]
+pub struct MemoryAttemptStore {
+    attempts: Mutex<HashMap<String, AttemptState>>,
+}
+
+impl AttemptStore for MemoryAttemptStore {
+    fn update(&self, key: &str, f: &mut dyn FnMut(Option<AttemptState>) -> Option<AttemptState>) {
+        let mut attempts = self.attempts.lock().unwrap();
+        match f(attempts.get(key).copied()) {
+            Some(state) => attempts.insert(key.to_string(), state),
+            None => attempts.remove(key),
+        };
+    }
+
+    fn prune(&self, now: Instant, reset_after: Duration) {
+        self.attempts.lock().unwrap().retain(|_, state| {
+            state.in_flight > 0
+                || state.blocked_until.is_some_and(|until| until > now)
+                || state.last_failure.is_some_and(|last| now.duration_since(last) < reset_after)
+        });
+    }
+}
+
+#[derive(Clone)]
+pub struct LoginRateLimiter {
+    store: Arc<dyn AttemptStore>,
+    account_limits: LoginLimits,
+    ip_limits: LoginLimits,
+}
+
+impl LoginRateLimiter {
+    pub fn new(store: Arc<dyn AttemptStore>, account_limits: LoginLimits, ip_limits: LoginLimits) -> Self {
+        LoginRateLimiter {
+            store,
+            account_limits,
+            ip_limits,
+        }
+    }
+
+    /// Reserve an attempt for `key`, counted as in flight until its response is recorded.
+    /// Errs with how long to wait if the key is blocked, or would be once the attempts
+    /// already in flight fail
+    pub fn reserve(&self, key: &str, limits: &LoginLimits, now: Instant) -> Result<(), Duration> {
+        let mut result = Ok(());
+        self.store.update(key, &mut |state| {
+            let mut state = state.unwrap_or_default();
+            let blocked = state.blocked_until.filter(|until| *until > now);
+            let pending = limits.backoff(state.recent_failures(limits, now) + state.in_flight);
+
+            if let Some(until) = blocked {
+                result = Err(until - now);
+            } else if state.in_flight > 0 && !pending.is_zero() {
+                result = Err(pending);
+            } else {
+                state.in_flight += 1;
+            }
+            Some(state)
+        });
+        result
+    }
+
+    /// Reserve an attempt on the client's IP and the account, or on neither
+    pub fn reserve_login(
+        &self,
+        ip_key: Option<String>,
+        account_key: Option<String>,
+        now: Instant,
+    ) -> Result<LoginReservation, Duration> {
+        let mut reservation = LoginReservation {
+            limiter: self.clone(),
+            ip_key: None,
+            account_key: None,
+        };
+        let mut wait = None;
+
+        if let Some(key) = ip_key {
+            match self.reserve(&key, &self.ip_limits, now) {
+                Ok(()) => reservation.ip_key = Some(key),
+                Err(retry_after) => wait = wait.max(Some(retry_after)),
+            }
+        }
+        if let Some(key) = account_key {
+            match self.reserve(&key, &self.account_limits, now) {
+                Ok(()) => reservation.account_key = Some(key),
+                Err(retry_after) => wait = wait.max(Some(retry_after)),
+            }
+        }
+
+        // Dropping the reservation gives back whatever was reserved
+        match wait {
+            Some(retry_after) => Err(retry_after),
+            None => Ok(reservation),
+        }
+    }
+
+    pub fn record_failure(&self, key: &str, limits: &LoginLimits, now: Instant) {
+        self.store.update(key, &mut |state| {
+            let state = state.unwrap_or_default();
+            let failures = state.recent_failures(limits, now) + 1;
+            let backoff = limits.backoff(failures);
+
+            Some(AttemptState {
+                failures,
+                last_failure: Some(now),
+                blocked_until: (!backoff.is_zero()).then(|| now + backoff),
+                in_flight: state.in_flight.saturating_sub(1),
+            })
+        });
+    }
+
+    pub fn record_success(&self, key: &str) {
+        // Other attempts on the key may still be in flight
+        self.store.update(key, &mut |state| {
+            let in_flight = state?.in_flight.saturating_sub(1);
+            (in_flight > 0).then(|| AttemptState {
+                in_flight,
+                ..AttemptState::default()
+            })
+        });
+    }
+
+    /// Give back a reserved attempt that neither failed nor succeeded
+    pub fn release(&self, key: &str) {
+        self.store.update(key, &mut |state| {
+            state.map(|state| AttemptState {
+                in_flight: state.in_flight.saturating_sub(1),
+                ..state
+            })
+        });
+    }
+}
+
+/// The attempts one login request holds, released if it ends without an outcome
+pub struct LoginReservation {
+    limiter: LoginRateLimiter,
+    ip_key: Option<String>,
+    account_key: Option<String>,
+}
+
+impl LoginReservation {
+    pub fn fail(mut self, now: Instant) {
+        if let Some(key) = self.ip_key.take() {
+            self.limiter.record_failure(&key, &self.limiter.ip_limits, now);
+        }
+        if let Some(key) = self.account_key.take() {
+            self.limiter.record_failure(&key, &self.limiter.account_limits, now);
+        }
+    }
+
+    pub fn succeed(mut self) {
+        if let Some(key) = self.account_key.take() {
+            self.limiter.record_success(&key);
+        }
+    }
+}
+
+impl Drop for LoginReservation {
+    fn drop(&mut self) {
+        for key in self.ip_key.iter().chain(&self.account_key) {
+            self.limiter.release(key);
+        }
+    }
+}
+
+impl Default for LoginRateLimiter {
+    fn default() -> Self {
+        Self::new(
+            Arc::new(MemoryAttemptStore::default()),
+            LoginLimits::per_account(),
+            LoginLimits::per_ip(),
+        )
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct LoginRateLimitLayer {
+    limiter: LoginRateLimiter,
+}
+
+impl LoginRateLimitLayer {
+    pub fn new(limiter: LoginRateLimiter) -> Self {
+        LoginRateLimitLayer { limiter }
+    }
+}
+
+impl<S> Layer<S> for LoginRateLimitLayer {
+    type Service = LoginRateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        LoginRateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LoginRateLimit<S> {
+    inner: S,
+    limiter: LoginRateLimiter,
+}
+
+#[derive(Deserialize)]
+struct LoginAccount {
+    email: Option<String>,
+}
+
+impl<S> Service<Request> for LoginRateLimit<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // The clone may not be ready, keep the one that was polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let ip_key = req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| format!("ip:{}", addr.ip()));
+
+            // Buffer the body to find the account, then hand it on unchanged
+            let (parts, body) = req.into_parts();
+            let bytes = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    let body = Json(json!({ "error": "Request body too large" }));
+                    return Ok((StatusCode::PAYLOAD_TOO_LARGE, body).into_response());
+                }
+            };
+            let account_key = serde_json::from_slice::<LoginAccount>(&bytes)
+                .ok()
+                .and_then(|account| account.email)
+                .map(|email| format!("account:{}", email.trim().to_lowercase()));
+            let req = Request::from_parts(parts, Body::from(bytes));
+
+            let now = Instant::now();
+            limiter.store.prune(now, limiter.account_limits.reset_after.max(limiter.ip_limits.reset_after));
+
+            // Reserved before the handler runs, so concurrent attempts can't all slip through
+            let reservation = match limiter.reserve_login(ip_key, account_key, now) {
+                Ok(reservation) => reservation,
+                Err(retry_after) => {
+                    let seconds = retry_after.as_secs().max(1);
+                    let body = Json(json!({
+                        "error": "Too many login attempts, try again later",
+                        "retry_after": seconds,
+                    }));
+                    return Ok((
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(RETRY_AFTER, seconds.to_string())],
+                        body,
+                    )
+                        .into_response());
+                }
+            };
+
+            let response = inner.call(req).await?;
+
+            // Only wrong credentials count, validation errors and the like give the attempt back
+            if response.status() == StatusCode::UNAUTHORIZED {
+                reservation.fail(Instant::now());
+            } else if response.status().is_success() {
+                reservation.succeed();
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: &str = "account:mike@example.com";
+    const IP: &str = "ip:127.0.0.1";
+
+    fn fail(limiter: &LoginRateLimiter, key: &str, limits: &LoginLimits, now: Instant) -> Result<(), Duration> {
+        limiter.reserve(key, limits, now)?;
+        limiter.record_failure(key, limits, now);
+        Ok(())
+    }
+
+    /// Wait out any backoff, then fail an attempt
+    fn fail_when_allowed(limiter: &LoginRateLimiter, key: &str, limits: &LoginLimits, now: &mut Instant) {
+        if let Err(wait) = limiter.reserve(key, limits, *now) {
+            *now += wait;
+        } else {
+            limiter.release(key);
+        }
+        fail(limiter, key, limits, *now).unwrap();
+    }
+
+    #[test]
+    fn free_failures_are_not_delayed() {
+        let limiter = LoginRateLimiter::default();
+        let limits = LoginLimits::per_account();
+        let now = Instant::now();
+
+        for _ in 0..limits.free_failures {
+            assert_eq!(fail(&limiter, ACCOUNT, &limits, now), Ok(()));
+        }
+
+        // The next attempt is still let through, its failure starts the backoff
+        assert_eq!(fail(&limiter, ACCOUNT, &limits, now), Ok(()));
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now), Err(limits.base_backoff));
+    }
+
+    #[test]
+    fn backoff_doubles_with_each_failure() {
+        let limiter = LoginRateLimiter::default();
+        let limits = LoginLimits::per_account();
+        let mut now = Instant::now();
+
+        for _ in 0..=limits.free_failures {
+            fail_when_allowed(&limiter, ACCOUNT, &limits, &mut now);
+        }
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now), Err(Duration::from_secs(1)));
+
+        fail_when_allowed(&limiter, ACCOUNT, &limits, &mut now);
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now), Err(Duration::from_secs(2)));
+        assert_eq!(
+            limiter.reserve(ACCOUNT, &limits, now + Duration::from_millis(1500)),
+            Err(Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn lockout_starts_at_five_failures_and_lasts_fifteen_minutes() {
+        let limiter = LoginRateLimiter::default();
+        let limits = LoginLimits::per_account();
+        let mut now = Instant::now();
+
+        for _ in 0..4 {
+            fail_when_allowed(&limiter, ACCOUNT, &limits, &mut now);
+        }
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now), Err(Duration::from_secs(2)));
+
+        fail_when_allowed(&limiter, ACCOUNT, &limits, &mut now);
+        let lockout = Duration::from_secs(15 * 60);
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now), Err(lockout));
+        assert_eq!(
+            limiter.reserve(ACCOUNT, &limits, now + lockout - Duration::from_secs(1)),
+            Err(Duration::from_secs(1))
+        );
+
+        // The failures are forgotten with the lockout, the next ones are free again
+        assert_eq!(fail(&limiter, ACCOUNT, &limits, now + lockout), Ok(()));
+        assert_eq!(limiter.reserve(ACCOUNT, &limits, now + lockout), Ok(()));
+    }
+
+    #[test]
+    fn ip_and_account_limits_are_independent() {
+        let limiter = LoginRateLimiter::default();
+        let account_limits = LoginLimits::per_account();
+        let ip_limits = LoginLimits::per_ip();
+        let now = Instant::now();
+
+        // Failing on many accounts from one IP only blocks the IP
+        for n in 0..=ip_limits.free_failures {
+            let account = format!("account:player{}@example.com", n);
+            limiter.reserve_login(Some(IP.to_string()), Some(account), now).unwrap().fail(now);
+        }
+        assert_eq!(limiter.reserve(IP, &ip_limits, now), Err(ip_limits.base_backoff));
+        assert_eq!(limiter.reserve("account:player0@example.com", &account_limits, now), Ok(()));
+
+        // Locking out an account from other IPs doesn't block this one
+        let mut later = now + ip_limits.reset_after;
+        for _ in 0..account_limits.lockout_after {
+            fail_when_allowed(&limiter, ACCOUNT, &account_limits, &mut later);
+        }
+        assert_eq!(limiter.reserve(IP, &ip_limits, later), Ok(()));
+        limiter.release(IP);
+
+        // A login needs both, the IP attempt is given back when the account is blocked
+        let blocked = limiter.reserve_login(Some(IP.to_string()), Some(ACCOUNT.to_string()), later);
+        assert_eq!(blocked.err(), Some(account_limits.lockout));
+        assert!(limiter.reserve_login(Some(IP.to_string()), None, later).is_ok());
+    }
+
+    #[test]
+    fn attempts_in_flight_count_towards_the_backoff() {
+        let limiter = LoginRateLimiter::default();
+        let limits = LoginLimits::per_account();
+        let now = Instant::now();
+        let account = || Some(ACCOUNT.to_string());
+
+        for _ in 0..limits.free_failures {
+            fail(&limiter, ACCOUNT, &limits, now).unwrap();
+        }
+
+        // Only one attempt is left before the backoff, a second one has to wait for it
+        let first = limiter.reserve_login(None, account(), now).unwrap();
+        assert_eq!(limiter.reserve_login(None, account(), now).err(), Some(limits.base_backoff));
+
+        // A response that isn't a failure gives the attempt back
+        drop(first);
+        let second = limiter.reserve_login(None, account(), now).unwrap();
+        second.succeed();
+        assert!(limiter.reserve_login(None, account(), now).is_ok());
+    }
+}
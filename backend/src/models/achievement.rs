@@ -20,10 +20,17 @@ pub struct PlayerAchievement {
     pub id: Uuid,
     pub user_id: Uuid,
     pub achievement_id: String,
-    pub unlocked_at: DateTime<Utc>,
+    pub unlocked_at: Option<DateTime<Utc>>, // NULL while the achievement is still in progress
     pub progress: serde_json::Value,
 }
 
+/// Stored in `player_achievements.progress`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementProgress {
+    pub current: i64,
+    pub target: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct AchievementWithStatus {
     pub id: String,
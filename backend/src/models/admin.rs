@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::{GameSession, USER_ROLES};
+use crate::validation::{Validate, Validator};
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct AuditLogEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Uuid,
+    pub details: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ModerationRequest {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetRoleRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminListQuery {
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlaggedSessionsResponse {
+    pub sessions: Vec<GameSession>,
+    pub page: i32,
+    pub total: i64,
+}
+
+impl Validate for ModerationRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(reason) = &self.reason {
+            v.length(reason.trim(), "reason", 1, 500);
+        }
+    }
+}
+
+impl Validate for SetRoleRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            USER_ROLES.contains(&self.role.as_str()),
+            "role",
+            format!("must be one of {:?}", USER_ROLES),
+        );
+    }
+}
+
+// Audit log actions
+pub const AUDIT_DEACTIVATE_USER: &str = "deactivate_user";
+pub const AUDIT_REACTIVATE_USER: &str = "reactivate_user";
+pub const AUDIT_SET_ROLE: &str = "set_role";
+pub const AUDIT_WIPE_LEADERBOARD: &str = "wipe_leaderboard";
+pub const AUDIT_VOID_SESSION: &str = "void_session";
+pub const AUDIT_RESCORE: &str = "rescore";
@@ -23,6 +23,7 @@ pub struct DailyChallengeCompletion {
     pub completed_at: DateTime<Utc>,
     pub score: Option<i32>,
     pub time_seconds: Option<i32>,
+    pub session_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,9 +35,7 @@ pub struct ChallengeResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct CompleteChallengeRequest {
-    pub score: Option<i32>,
-    pub time_seconds: Option<i32>,
-    pub session_id: Option<Uuid>,
+    pub session_id: Option<Uuid>, // required; score and time are taken from the session
 }
 
 // Challenge types
@@ -45,7 +44,6 @@ pub const CHALLENGE_TYPES: &[&str] = &[
     "no_cameras",       // Survive without using cameras
     "no_left_door",     // Survive without left door
     "no_right_door",    // Survive without right door
-    "speed_run",        // Complete night in target time
     "specific_night",   // Complete a specific night
     "collect_pizza",    // Find X pizza slices in free roam
     "photo_challenge",  // Take photos of specific animatronics
@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::UserPublic;
+use crate::validation::{Validate, Validator};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Friendship {
+    pub id: Uuid,
+    pub requester_id: Uuid,
+    pub addressee_id: Uuid,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SendFriendRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendResponse {
+    pub user: UserPublic,
+    pub since: Option<DateTime<Utc>>,
+    pub presence: String, // 'in_game', 'online', 'offline'
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendRequestResponse {
+    pub id: Uuid,
+    pub user: UserPublic, // the other side of the request
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendRequestsResponse {
+    pub incoming: Vec<FriendRequestResponse>,
+    pub outgoing: Vec<FriendRequestResponse>,
+}
+
+impl Validate for SendFriendRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.username.trim().is_empty(), "username", "is required");
+    }
+}
+
+// Friendship statuses
+pub const FRIENDSHIP_PENDING: &str = "pending";
+pub const FRIENDSHIP_ACCEPTED: &str = "accepted";
+
+// Presence shown in the friends list
+pub const PRESENCE_IN_GAME: &str = "in_game";
+pub const PRESENCE_ONLINE: &str = "online";
+pub const PRESENCE_OFFLINE: &str = "offline";
+
+/// Users seen this recently count as online
+pub const ONLINE_WINDOW_MINUTES: i64 = 5;
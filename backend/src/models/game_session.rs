@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use super::pizza::PIZZA_SLICE_COUNT;
+use crate::validation::{Validate, Validator};
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct GameSession {
     pub id: Uuid,
@@ -22,6 +25,24 @@ pub struct GameSession {
     pub photos_taken: i32,
     pub easy_mode: bool,
     pub custom_difficulty: Option<serde_json::Value>,
+    pub cameras_used: Option<bool>,
+    pub power_out: bool,
+    pub events: serde_json::Value,
+    pub starting_power: i32,
+    pub left_door_used: Option<bool>,
+    pub right_door_used: Option<bool>,
+    pub status: String,
+    pub last_heartbeat_at: Option<DateTime<Utc>>,
+    pub flagged_reason: Option<String>,
+    pub scoring_version: Option<i32>,
+    pub seed: Option<i64>, // night simulation seed, picked by the server
+}
+
+impl GameSession {
+    /// Whether the session can still receive heartbeats and be finalized
+    pub fn is_open(&self) -> bool {
+        self.status == SESSION_STARTED || self.status == SESSION_IN_PROGRESS
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +51,7 @@ pub struct CreateSessionRequest {
     pub night_number: Option<i32>,
     pub easy_mode: Option<bool>,
     pub custom_difficulty: Option<serde_json::Value>,
+    pub starting_power: Option<i32>, // lower for power_limit challenges, defaults to 100
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,11 +60,48 @@ pub struct UpdateSessionRequest {
     pub final_power: Option<i32>,
     pub time_survived_seconds: Option<i32>,
     pub star_rating: Option<i32>,
-    pub score: Option<i32>,
     pub death_by: Option<String>,
     pub power_ups_used: Option<serde_json::Value>,
     pub pizza_slices_found: Option<i32>,
     pub photos_taken: Option<i32>,
+    pub cameras_used: Option<bool>,
+    pub power_out: Option<bool>,
+    pub events: Option<serde_json::Value>,
+    pub left_door_used: Option<bool>,
+    pub right_door_used: Option<bool>,
+    pub replay: Option<ReplayLog>, // required for night sessions, the result is re-run on the server
+}
+
+/// Progress reported while a session is running, never touches profile stats
+#[derive(Debug, Deserialize)]
+pub struct SessionHeartbeatRequest {
+    pub time_survived_seconds: Option<i32>,
+    pub final_power: Option<i32>, // power at the time of the heartbeat
+    pub pizza_slices_found: Option<i32>,
+    pub photos_taken: Option<i32>,
+    pub events: Option<serde_json::Value>,
+}
+
+/// Timestamped guard inputs for a night session, re-run with the session's seed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayLog {
+    pub inputs: Vec<ReplayInput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayInput {
+    pub at_ms: u64, // since the night started
+    pub action_type: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct SessionReplay {
+    pub session_id: Uuid,
+    pub seed: i64,
+    pub inputs: serde_json::Value,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
@@ -51,4 +110,90 @@ pub struct SessionResponse {
     pub session_type: String,
     pub night_number: Option<i32>,
     pub started_at: DateTime<Utc>,
+    pub seed: i64, // the client simulates the night with this seed
+}
+
+pub const SESSION_TYPES: &[&str] = &["night", "freeroam", "survival", "multiplayer"];
+
+// Session event ids
+pub const EVENT_GOLDEN_FREDDY_SEEN: &str = "golden_freddy_seen";
+pub const EVENT_GOLDEN_FREDDY_PHOTO: &str = "golden_freddy_photo";
+pub const EVENT_MINIGAME_PREFIX: &str = "minigame:"; // followed by the minigame id
+pub const EVENT_PHOTO_PREFIX: &str = "photo:"; // followed by the photographed animatronic
+
+// Session states
+pub const SESSION_STARTED: &str = "started";
+pub const SESSION_IN_PROGRESS: &str = "in_progress";
+pub const SESSION_FINISHED: &str = "finished";
+pub const SESSION_ABANDONED: &str = "abandoned";
+pub const SESSION_REJECTED: &str = "rejected"; // failed the plausibility checks, see flagged_reason
+pub const SESSION_VOIDED: &str = "voided"; // voided by a moderator
+
+/// Open sessions without a heartbeat for this long are abandoned
+pub const SESSION_TIMEOUT_MINUTES: i64 = 15;
+
+impl Validate for CreateSessionRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            SESSION_TYPES.contains(&self.session_type.as_str()),
+            "session_type",
+            format!("must be one of {:?}", SESSION_TYPES),
+        );
+        v.check(
+            self.session_type != "night" || self.night_number.is_some(),
+            "night_number",
+            "is required for night sessions",
+        );
+        v.range(self.night_number, "night_number", 1, 7);
+        v.range(self.starting_power, "starting_power", 1, 100);
+
+        if let Some(custom) = &self.custom_difficulty {
+            let levels_valid = custom.as_object().map_or(false, |levels| {
+                ["freddy", "bonnie", "chica", "foxy"].iter().all(|name| {
+                    levels
+                        .get(*name)
+                        .and_then(|level| level.as_i64())
+                        .map_or(false, |level| (0..=20).contains(&level))
+                })
+            });
+            v.check(
+                levels_valid,
+                "custom_difficulty",
+                "must set freddy, bonnie, chica and foxy to a level between 0 and 20",
+            );
+        }
+    }
+}
+
+// Ranges of the reported result are left to the anti-cheat checks, which flag them for review
+impl Validate for UpdateSessionRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(death_by) = &self.death_by {
+            v.length(death_by, "death_by", 1, 50);
+        }
+        v.check(
+            self.power_ups_used.as_ref().map_or(true, |p| p.is_array()),
+            "power_ups_used",
+            "must be an array",
+        );
+        v.string_list(self.events.as_ref(), "events", 500, 100);
+
+        if let Some(replay) = &self.replay {
+            v.check(
+                replay.inputs.iter().all(|input| !input.action_type.is_empty()),
+                "replay.inputs",
+                "every input needs an action_type",
+            );
+        }
+    }
+}
+
+impl Validate for SessionHeartbeatRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.range(self.time_survived_seconds, "time_survived_seconds", 0, i32::MAX);
+        v.range(self.final_power, "final_power", 0, 100);
+        v.range(self.pizza_slices_found, "pizza_slices_found", 0, PIZZA_SLICE_COUNT);
+        v.range(self.photos_taken, "photos_taken", 0, i32::MAX);
+        v.string_list(self.events.as_ref(), "events", 500, 100);
+    }
 }
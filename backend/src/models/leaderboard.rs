@@ -1,8 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use super::{GameSession, ROOM_ROLE_ANIMATRONIC, ROOM_ROLE_GUARD};
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct LeaderboardEntry {
     pub id: Uuid,
@@ -10,23 +12,54 @@ pub struct LeaderboardEntry {
     pub username: String,
     pub leaderboard_type: String,
     pub score: i64,
+    #[serde(skip_serializing)]
+    pub rank_score: i64, // score mapped so higher is better, see `LeaderboardDefinition::rank_score`
     pub additional_data: Option<serde_json::Value>,
     pub achieved_at: DateTime<Utc>,
+    pub period: String,
+    pub period_start: NaiveDate,
+    pub session_id: Option<Uuid>,
+    pub time_seconds: Option<i32>,
+    pub season_id: Uuid,
+}
+
+impl LeaderboardEntry {
+    pub fn with_rank(self, rank: i64) -> LeaderboardEntryWithRank {
+        LeaderboardEntryWithRank {
+            rank,
+            id: self.id,
+            user_id: self.user_id,
+            username: self.username,
+            score: self.score,
+            additional_data: self.additional_data,
+            achieved_at: self.achieved_at,
+            session_id: self.session_id,
+            time_seconds: self.time_seconds,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct LeaderboardEntryWithRank {
     pub rank: i64,
+    pub id: Uuid,
     pub user_id: Uuid,
     pub username: String,
     pub score: i64,
     pub additional_data: Option<serde_json::Value>,
     pub achieved_at: DateTime<Utc>,
+    pub session_id: Option<Uuid>,
+    pub time_seconds: Option<i32>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct LeaderboardResponse {
     pub leaderboard_type: String,
+    pub board: &'static LeaderboardDefinition,
+    pub season_id: Uuid,
+    pub scope: String,
+    pub timeframe: String,
+    pub period_start: NaiveDate,
     pub entries: Vec<LeaderboardEntryWithRank>,
     pub page: i32,
     pub total_pages: i32,
@@ -38,26 +71,234 @@ pub struct LeaderboardQuery {
     pub page: Option<i32>,
     pub limit: Option<i32>,
     pub timeframe: Option<String>, // 'daily', 'weekly', 'monthly', 'all'
+    pub date: Option<NaiveDate>,   // any day in an earlier period, defaults to today
+    pub scope: Option<String>,     // 'global', 'friends'
+    pub season_id: Option<Uuid>,   // defaults to the current season
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardPeriodsResponse {
+    pub leaderboard_type: String,
+    pub season_id: Uuid,
+    pub timeframe: String,
+    pub periods: Vec<NaiveDate>, // period starts with entries, newest first
+}
+
+/// A leaderboard entry with the run behind it
+#[derive(Debug, Serialize)]
+pub struct LeaderboardRunResponse {
+    pub entry: LeaderboardEntry,
+    pub rank: Option<i64>,
+    pub session: Option<GameSession>,
+    pub has_replay: bool,
+}
+
+/// The caller's entry with the nearest rivals on either side
+#[derive(Debug, Serialize)]
+pub struct AroundMeResponse {
+    pub leaderboard_type: String,
+    pub season_id: Uuid,
+    pub scope: String,
+    pub timeframe: String,
+    pub period_start: NaiveDate,
+    pub me: Option<LeaderboardEntryWithRank>,
+    pub above: Vec<LeaderboardEntryWithRank>, // best first
+    pub below: Vec<LeaderboardEntryWithRank>,
+    pub total_players: i64,
+    pub percentile: Option<f64>, // share of the other players the caller beats, 0-100
 }
 
 #[derive(Debug, Serialize)]
 pub struct MyRankResponse {
+    pub season_id: Uuid,
+    pub scope: String,
+    pub timeframe: String,
+    pub period_start: NaiveDate,
     pub rank: Option<i64>,
     pub score: Option<i64>,
     pub total_players: i64,
 }
 
-// Leaderboard types
-pub const LEADERBOARD_TYPES: &[&str] = &[
-    "night_1",
-    "night_2",
-    "night_3",
-    "night_4",
-    "night_5",
-    "night_6",
-    "night_7",
-    "survival",
-    "speed_run",
-    "photos",
-    "pizza_collection",
+/// Which way a board is ranked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    HigherIsBetter,
+    LowerIsBetter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreUnit {
+    Points,
+    Seconds,
+    Count,
+    Rating,
+}
+
+/// Where a board's scores come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreSource {
+    Session(&'static str), // finished sessions of this type
+    AnySession,
+    PizzaSlices,          // the pizza slice collection, not sessions
+    Rating(&'static str), // ranked versus rating in this role
+}
+
+#[derive(Debug, Serialize)]
+pub struct LeaderboardDefinition {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub sort: SortDirection,
+    pub unit: ScoreUnit,
+    pub source: ScoreSource,
+    pub easy_mode_eligible: bool,
+}
+
+impl LeaderboardDefinition {
+    /// `score` mapped so that a higher value is always better, this is what entries are ranked by
+    pub fn rank_score(&self, score: i64) -> i64 {
+        match self.sort {
+            SortDirection::HigherIsBetter => score,
+            SortDirection::LowerIsBetter => -score,
+        }
+    }
+
+    /// Whether entries keep the player's best score, rating boards show the latest rating instead
+    pub fn keeps_best(&self) -> bool {
+        !matches!(self.source, ScoreSource::Rating(_))
+    }
+
+    /// Whether a finished session may be submitted to this board
+    pub fn accepts(&self, session: &GameSession) -> bool {
+        let source_matches = match self.source {
+            ScoreSource::Session(session_type) => session.session_type == session_type,
+            ScoreSource::AnySession => true,
+            ScoreSource::PizzaSlices | ScoreSource::Rating(_) => false,
+        };
+
+        source_matches && (self.easy_mode_eligible || !session.easy_mode)
+    }
+}
+
+const fn night_board(id: &'static str, name: &'static str) -> LeaderboardDefinition {
+    LeaderboardDefinition {
+        id,
+        name,
+        sort: SortDirection::HigherIsBetter,
+        unit: ScoreUnit::Points,
+        source: ScoreSource::Session("night"),
+        easy_mode_eligible: true, // easy mode scores are halved instead
+    }
+}
+
+const fn ranked_board(id: &'static str, name: &'static str, role: &'static str) -> LeaderboardDefinition {
+    LeaderboardDefinition {
+        id,
+        name,
+        sort: SortDirection::HigherIsBetter,
+        unit: ScoreUnit::Rating,
+        source: ScoreSource::Rating(role),
+        easy_mode_eligible: false,
+    }
+}
+
+// Leaderboard registry
+pub const LEADERBOARDS: &[LeaderboardDefinition] = &[
+    night_board("night_1", "Night 1"),
+    night_board("night_2", "Night 2"),
+    night_board("night_3", "Night 3"),
+    night_board("night_4", "Night 4"),
+    night_board("night_5", "Night 5"),
+    night_board("night_6", "Night 6"),
+    night_board("night_7", "Night 7"),
+    LeaderboardDefinition {
+        id: "survival",
+        name: "Survival",
+        sort: SortDirection::HigherIsBetter,
+        unit: ScoreUnit::Points,
+        source: ScoreSource::Session("survival"),
+        easy_mode_eligible: true,
+    },
+    LeaderboardDefinition {
+        id: "speed_run",
+        name: "Speed Run",
+        sort: SortDirection::LowerIsBetter,
+        unit: ScoreUnit::Seconds,
+        source: ScoreSource::Session("night"),
+        easy_mode_eligible: false,
+    },
+    LeaderboardDefinition {
+        id: "photos",
+        name: "Photographer",
+        sort: SortDirection::HigherIsBetter,
+        unit: ScoreUnit::Count,
+        source: ScoreSource::AnySession,
+        easy_mode_eligible: true,
+    },
+    LeaderboardDefinition {
+        id: "pizza_collection",
+        name: "Pizza Collection",
+        sort: SortDirection::HigherIsBetter,
+        unit: ScoreUnit::Count,
+        source: ScoreSource::PizzaSlices,
+        easy_mode_eligible: true,
+    },
+    ranked_board("ranked_guard", "Ranked Guard", ROOM_ROLE_GUARD),
+    ranked_board("ranked_animatronic", "Ranked Animatronic", ROOM_ROLE_ANIMATRONIC),
+];
+
+pub fn find_leaderboard(id: &str) -> Option<&'static LeaderboardDefinition> {
+    LEADERBOARDS.iter().find(|board| board.id == id)
+}
+
+/// The board ranking ratings in a versus role
+pub fn ranked_board_id(role: &str) -> String {
+    format!("ranked_{}", role)
+}
+
+// Leaderboard scopes, friends boards rank the user among their accepted friends
+pub const SCOPE_GLOBAL: &str = "global";
+pub const SCOPE_FRIENDS: &str = "friends";
+
+/// Window a leaderboard is ranked over, periods are in UTC and weeks start on Monday
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeframe {
+    Daily,
+    Weekly,
+    Monthly,
+    All,
+}
+
+pub const TIMEFRAMES: &[Timeframe] = &[
+    Timeframe::Daily,
+    Timeframe::Weekly,
+    Timeframe::Monthly,
+    Timeframe::All,
 ];
+
+impl Timeframe {
+    pub fn parse(value: &str) -> Option<Self> {
+        TIMEFRAMES.iter().copied().find(|t| t.as_str() == value)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Timeframe::Daily => "daily",
+            Timeframe::Weekly => "weekly",
+            Timeframe::Monthly => "monthly",
+            Timeframe::All => "all",
+        }
+    }
+
+    /// First day of the period containing `date`
+    pub fn period_start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Timeframe::Daily => date,
+            Timeframe::Weekly => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+            Timeframe::Monthly => date.with_day(1).unwrap_or(date),
+            Timeframe::All => NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or(NaiveDate::MIN),
+        }
+    }
+}
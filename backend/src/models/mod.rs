@@ -4,6 +4,11 @@ pub mod achievement;
 pub mod challenge;
 pub mod leaderboard;
 pub mod multiplayer;
+pub mod pizza;
+pub mod admin;
+pub mod friend;
+pub mod season;
+pub mod ranked;
 
 pub use user::*;
 pub use game_session::*;
@@ -11,3 +16,8 @@ pub use achievement::*;
 pub use challenge::*;
 pub use leaderboard::*;
 pub use multiplayer::*;
+pub use pizza::*;
+pub use admin::*;
+pub use friend::*;
+pub use season::*;
+pub use ranked::*;
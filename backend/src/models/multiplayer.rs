@@ -16,6 +16,7 @@ pub struct MultiplayerRoom {
     pub created_at: DateTime<Utc>,
     pub started_at: Option<DateTime<Utc>>,
     pub ended_at: Option<DateTime<Utc>>,
+    pub ranked: bool, // made by the matchmaking queue, roles are fixed and the result is rated
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -27,6 +28,8 @@ pub struct MultiplayerParticipant {
     pub role: Option<String>,
     pub joined_at: DateTime<Utc>,
     pub is_ready: bool,
+    #[serde(skip_serializing)]
+    pub guest_token_hash: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +50,14 @@ pub struct RoomResponse {
     pub participants: Vec<ParticipantInfo>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct JoinRoomResponse {
+    #[serde(flatten)]
+    pub room: RoomResponse,
+    pub participant_id: Uuid,
+    pub guest_token: Option<String>, // only issued to guests, used to authenticate the WebSocket
+}
+
 #[derive(Debug, Serialize)]
 pub struct ParticipantInfo {
     pub id: Uuid,
@@ -56,13 +67,42 @@ pub struct ParticipantInfo {
     pub is_ready: bool,
 }
 
+// Room statuses
+pub const ROOM_WAITING: &str = "waiting";
+pub const ROOM_PLAYING: &str = "playing";
+pub const ROOM_FINISHED: &str = "finished";
+
+// Roles a participant can pick
+pub const ROOM_ROLE_GUARD: &str = "guard";
+pub const ROOM_ROLE_ANIMATRONIC: &str = "animatronic";
+pub const ROOM_ROLES: &[&str] = &[ROOM_ROLE_GUARD, ROOM_ROLE_ANIMATRONIC];
+
+// Rooms in this mode run the night simulation on the server
+pub const GAME_MODE_VERSUS: &str = "versus";
+
+// Credentials can also be sent as the first message instead
+#[derive(Debug, Deserialize)]
+pub struct WsConnectQuery {
+    pub token: Option<String>,
+    pub guest_token: Option<String>,
+    pub reconnect_token: Option<String>, // from the `Session` message, resumes a held slot
+    pub last_seq: Option<u64>,           // last event the client saw, later ones are replayed
+}
+
 // WebSocket message types
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
+    Auth {
+        token: Option<String>,
+        guest_token: Option<String>,
+        reconnect_token: Option<String>,
+        last_seq: Option<u64>,
+    },
     Ready,
     RoleSelect { role: String },
     GameAction { action: GameAction },
+    GameOver { result: serde_json::Value },
     Chat { message: String },
     Ping,
 }
@@ -73,13 +113,23 @@ pub struct GameAction {
     pub data: serde_json::Value,
 }
 
+// Room broadcasts carry a `seq` field next to `type`, messages sent to one player don't
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ServerMessage {
+    Session {
+        participant_id: Uuid,
+        reconnect_token: String, // rotated on every connect
+        seq: u64,                // latest room event when the socket (re)connected
+        grace_seconds: u64,      // how long the slot is held after a disconnect
+        resumed: bool,
+    },
     RoomState { room: RoomResponse },
     GameState { state: serde_json::Value },
     PlayerJoined { participant: ParticipantInfo },
     PlayerLeft { participant_id: Uuid },
+    PlayerDisconnected { participant_id: Uuid, grace_seconds: u64 },
+    PlayerReconnected { participant_id: Uuid },
     GameStart,
     GameEnd { result: serde_json::Value },
     Chat { from: String, message: String },
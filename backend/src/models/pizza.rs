@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PizzaSliceProgress {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub slice_id: String,
+    pub found_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct PizzaSliceLocation {
+    pub id: &'static str,
+    pub room: &'static str,
+    pub difficulty: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CollectSliceRequest {
+    pub slice_id: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PizzaSlicesResponse {
+    pub slices: Vec<PizzaSliceProgress>,
+    pub found: i32,
+    pub total: i32,
+}
+
+// Slice locations in free roam, matching the frontend's CollectibleManager
+pub const PIZZA_SLICES: &[PizzaSliceLocation] = &[
+    PizzaSliceLocation { id: "pizza_stage_1", room: "stage", difficulty: "easy" },
+    PizzaSliceLocation { id: "pizza_dining_1", room: "dining", difficulty: "easy" },
+    PizzaSliceLocation { id: "pizza_dining_2", room: "dining", difficulty: "medium" },
+    PizzaSliceLocation { id: "pizza_westHall_1", room: "westHall", difficulty: "medium" },
+    PizzaSliceLocation { id: "pizza_eastHall_1", room: "eastHall", difficulty: "medium" },
+    PizzaSliceLocation { id: "pizza_kitchen_1", room: "kitchen", difficulty: "hard" },
+    PizzaSliceLocation { id: "pizza_backstage_1", room: "backstage", difficulty: "hard" },
+    PizzaSliceLocation { id: "pizza_pirate_1", room: "pirateCove", difficulty: "extreme" },
+];
+
+pub const PIZZA_SLICE_COUNT: i32 = PIZZA_SLICES.len() as i32;
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::ROOM_ROLES;
+use crate::validation::{Validate, Validator};
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PlayerRating {
+    pub user_id: Uuid,
+    pub role: String,
+    pub rating: i32,
+    pub games_played: i32,
+    pub wins: i32,
+    pub losses: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RankedMatch {
+    pub id: Uuid,
+    pub room_id: Uuid,
+    pub guard_user_id: Option<Uuid>,
+    pub animatronic_user_id: Option<Uuid>,
+    pub guard_rating: i32,
+    pub animatronic_rating: i32,
+    pub winner_role: Option<String>,
+    pub guard_rating_change: Option<i32>,
+    pub animatronic_rating_change: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JoinQueueRequest {
+    pub role: String, // 'guard', 'animatronic', 'any'
+}
+
+impl Validate for JoinQueueRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.role == QUEUE_ROLE_ANY || ROOM_ROLES.contains(&self.role.as_str()),
+            "role",
+            format!("must be one of {:?} or \"{}\"", ROOM_ROLES, QUEUE_ROLE_ANY),
+        );
+    }
+}
+
+/// Where the caller stands in matchmaking
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueStatusResponse {
+    pub status: String, // 'idle', 'queued', 'matched'
+    pub role: Option<String>,
+    pub queued_at: Option<DateTime<Utc>>,
+    pub search_window: Option<i32>, // rating difference currently accepted
+    #[serde(rename = "match")]
+    pub matched: Option<MatchFound>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchFound {
+    pub room_code: String,
+    pub role: String,
+    pub rating: i32,
+    pub opponent: String,
+    pub opponent_rating: i32,
+}
+
+// Matchmaking socket messages
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum MatchmakingClientMessage {
+    Auth { token: String },
+    Join { role: String },
+    Leave,
+    Ping,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum MatchmakingServerMessage {
+    Queued { status: QueueStatusResponse },
+    MatchFound { matched: MatchFound },
+    Left,
+    Error { message: String },
+    Pong,
+}
+
+// Queue role for players happy with either side
+pub const QUEUE_ROLE_ANY: &str = "any";
+
+// Queue statuses
+pub const QUEUE_IDLE: &str = "idle";
+pub const QUEUE_QUEUED: &str = "queued";
+pub const QUEUE_MATCHED: &str = "matched";
+
+pub const DEFAULT_RATING: i32 = 1500;
+
+// Ranked matches are played on this night
+pub const RANKED_NIGHT: i32 = 4;
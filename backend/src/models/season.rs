@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Season {
+    pub id: Uuid,
+    pub number: i32,
+    pub name: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub reward_skin: Option<String>,
+    pub status: String,
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SeasonStanding {
+    pub id: Uuid,
+    pub season_id: Uuid,
+    pub leaderboard_type: String,
+    pub user_id: Uuid,
+    pub username: String,
+    pub final_rank: i64,
+    pub score: i64,
+    pub achieved_at: DateTime<Utc>,
+    pub session_id: Option<Uuid>,
+    pub reward_skin: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeasonStandingsQuery {
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeasonStandingsResponse {
+    pub season: Season,
+    pub leaderboard_type: String,
+    pub standings: Vec<SeasonStanding>,
+    pub page: i32,
+    pub total_pages: i32,
+    pub total_entries: i64,
+}
+
+/// Where a user finished on one board of an archived season
+#[derive(Debug, Serialize)]
+pub struct SeasonPlacement {
+    pub season_id: Uuid,
+    pub season_number: i32,
+    pub season_name: String,
+    pub leaderboard_type: String,
+    pub final_rank: i64,
+    pub score: i64,
+    pub reward_skin: Option<String>,
+}
+
+// Season statuses
+pub const SEASON_ACTIVE: &str = "active";
+pub const SEASON_ARCHIVED: &str = "archived";
+
+pub const SEASON_LENGTH_DAYS: i64 = 91;
+
+/// Players placing this high on any board get the season's reward
+pub const SEASON_REWARD_TOP: i64 = 10;
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use crate::validation::{Validate, Validator};
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
@@ -16,6 +18,9 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
     pub is_active: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub role: String,
+    pub last_seen_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -55,9 +60,56 @@ pub struct LoginRequest {
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
     pub user: UserPublic,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailTokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+// User roles, moderators and admins can use /api/admin
+pub const ROLE_PLAYER: &str = "player";
+pub const ROLE_MODERATOR: &str = "moderator";
+pub const ROLE_ADMIN: &str = "admin";
+pub const USER_ROLES: &[&str] = &[ROLE_PLAYER, ROLE_MODERATOR, ROLE_ADMIN];
+
+// Email token purposes
+pub const TOKEN_VERIFY_EMAIL: &str = "verify_email";
+pub const TOKEN_PASSWORD_RESET: &str = "password_reset";
+pub const VERIFY_EMAIL_TTL_HOURS: i64 = 48;
+pub const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub family_id: Uuid,
+    pub token_hash: String,
+    pub access_jti: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserPublic {
     pub id: Uuid,
@@ -86,3 +138,62 @@ pub struct UpdateProfileRequest {
     pub music_volume: Option<f32>,
     pub equipped_decorations: Option<serde_json::Value>,
 }
+
+impl Validate for RegisterRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.username(&self.username, "username");
+        v.email(&self.email, "email");
+        v.password(&self.password, "password");
+    }
+}
+
+impl Validate for LoginRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.email.is_empty(), "email", "is required");
+        v.check(!self.password.is_empty(), "password", "is required");
+    }
+}
+
+impl Validate for UpdateProfileRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(display_name) = &self.display_name {
+            v.length(display_name.trim(), "display_name", 1, 100);
+        }
+        if let Some(avatar_url) = &self.avatar_url {
+            v.length(avatar_url, "avatar_url", 1, 500);
+            v.check(
+                avatar_url.starts_with("https://") || avatar_url.starts_with("http://"),
+                "avatar_url",
+                "must be an http(s) URL",
+            );
+        }
+        v.range(self.audio_volume, "audio_volume", 0.0, 1.0);
+        v.range(self.music_volume, "music_volume", 0.0, 1.0);
+        v.string_list(self.equipped_decorations.as_ref(), "equipped_decorations", 20, 50);
+    }
+}
+
+impl Validate for RefreshRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.token(&self.refresh_token, "refresh_token");
+    }
+}
+
+impl Validate for EmailTokenRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.token(&self.token, "token");
+    }
+}
+
+impl Validate for PasswordResetRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.email(&self.email, "email");
+    }
+}
+
+impl Validate for ResetPasswordRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.token(&self.token, "token");
+        v.password(&self.new_password, "new_password");
+    }
+}
@@ -3,9 +3,14 @@ use axum::{
     Json,
 };
 use chrono::Utc;
-use uuid::Uuid;
 
-use crate::{error::AppError, models::*, routes::auth::Claims, AppState};
+use crate::{
+    error::AppError,
+    models::*,
+    routes::auth::Claims,
+    services::{AchievementService, RequirementContext},
+    AppState,
+};
 
 pub async fn list_all(
     State(state): State<AppState>,
@@ -51,6 +56,13 @@ pub async fn get_mine(
                 unlocked_count += 1;
             }
 
+            // Secret achievements don't reveal how close the player is
+            let progress = if row.is_secret && !unlocked {
+                None
+            } else {
+                row.progress
+            };
+
             AchievementWithStatus {
                 id: row.id,
                 name_key: row.name_key,
@@ -61,7 +73,7 @@ pub async fn get_mine(
                 is_secret: row.is_secret,
                 unlocked,
                 unlocked_at: row.unlocked_at,
-                progress: row.progress,
+                progress,
             }
         })
         .collect();
@@ -98,34 +110,35 @@ pub async fn claim(
     .fetch_optional(&state.db)
     .await?;
 
-    if let Some(pa) = existing {
+    if let Some(pa) = existing.filter(|pa| pa.unlocked_at.is_some()) {
         return Ok(Json(pa));
     }
 
-    // TODO: Validate requirements based on player profile
-    // For now, just grant it
+    let requirements = AchievementService::parse_requirements(&achievement)?;
+    let ctx = RequirementContext::load(&mut *state.db.acquire().await?, claims.sub).await?;
+    let unmet = ctx.unmet(&requirements);
 
-    let player_achievement_id = Uuid::new_v4();
-    let now = Utc::now();
+    if !unmet.is_empty() {
+        let missing: Vec<String> = unmet.iter().map(|r| r.describe()).collect();
+        return Err(AppError::Forbidden(format!(
+            "Requirements not met: {}",
+            missing.join(", ")
+        )));
+    }
 
-    sqlx::query!(
-        r#"
-        INSERT INTO player_achievements (id, user_id, achievement_id, unlocked_at)
-        VALUES ($1, $2, $3, $4)
-        "#,
-        player_achievement_id,
+    let progress = ctx.progress(&requirements);
+    AchievementService::save_progress(&state.db, claims.sub, &achievement_id, &progress, Some(Utc::now()))
+        .await?;
+
+    // Re-read in case a concurrent request unlocked it first
+    let player_achievement = sqlx::query_as!(
+        PlayerAchievement,
+        "SELECT * FROM player_achievements WHERE user_id = $1 AND achievement_id = $2",
         claims.sub,
-        achievement_id,
-        now,
+        achievement_id
     )
-    .execute(&state.db)
+    .fetch_one(&state.db)
     .await?;
 
-    Ok(Json(PlayerAchievement {
-        id: player_achievement_id,
-        user_id: claims.sub,
-        achievement_id,
-        unlocked_at: now,
-        progress: serde_json::json!({}),
-    }))
+    Ok(Json(player_achievement))
 }
@@ -0,0 +1,411 @@
+// Admin routes - moderation actions for staff, every action is written to the audit log
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::{PgExecutor, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    middleware::{Admin, Staff},
+    models::*,
+    scoring,
+    services::{ScoringService, SeasonService},
+    validation::ValidatedJson,
+    AppState,
+};
+
+pub async fn deactivate_user(
+    State(state): State<AppState>,
+    staff: Staff,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<ModerationRequest>,
+) -> Result<StatusCode, AppError> {
+    if user_id == staff.claims.sub {
+        return Err(AppError::BadRequest("You cannot deactivate yourself".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+    check_target(&mut tx, &staff, user_id).await?;
+
+    sqlx::query!("UPDATE users SET is_active = false WHERE id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Revoking the refresh tokens also invalidates the access tokens issued with them
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+        Utc::now(),
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit(
+        &mut *tx,
+        staff.claims.sub,
+        AUDIT_DEACTIVATE_USER,
+        "user",
+        user_id,
+        json!({ "reason": req.reason }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn reactivate_user(
+    State(state): State<AppState>,
+    staff: Staff,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<ModerationRequest>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db.begin().await?;
+    check_target(&mut tx, &staff, user_id).await?;
+
+    sqlx::query!("UPDATE users SET is_active = true WHERE id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit(
+        &mut *tx,
+        staff.claims.sub,
+        AUDIT_REACTIVATE_USER,
+        "user",
+        user_id,
+        json!({ "reason": req.reason }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn set_role(
+    State(state): State<AppState>,
+    Admin(claims): Admin,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<SetRoleRequest>,
+) -> Result<StatusCode, AppError> {
+    // Keeps the last admin from demoting themselves
+    if user_id == claims.sub {
+        return Err(AppError::BadRequest("You cannot change your own role".to_string()));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let previous = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1 FOR UPDATE", user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    sqlx::query!("UPDATE users SET role = $1 WHERE id = $2", req.role, user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    audit(
+        &mut *tx,
+        claims.sub,
+        AUDIT_SET_ROLE,
+        "user",
+        user_id,
+        json!({ "from": previous, "to": req.role }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn wipe_leaderboard(
+    State(state): State<AppState>,
+    staff: Staff,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<ModerationRequest>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db.begin().await?;
+    check_target(&mut tx, &staff, user_id).await?;
+
+    let removed = sqlx::query!("DELETE FROM leaderboard_entries WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    audit(
+        &mut *tx,
+        staff.claims.sub,
+        AUDIT_WIPE_LEADERBOARD,
+        "user",
+        user_id,
+        json!({ "reason": req.reason, "entries_removed": removed }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Void a session and remove the leaderboard entries it produced
+pub async fn void_session(
+    State(state): State<AppState>,
+    staff: Staff,
+    Path(session_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<ModerationRequest>,
+) -> Result<StatusCode, AppError> {
+    let mut tx = state.db.begin().await?;
+
+    let session = sqlx::query_as!(
+        GameSession,
+        "SELECT * FROM game_sessions WHERE id = $1 FOR UPDATE",
+        session_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    if session.status == SESSION_VOIDED {
+        return Err(AppError::Conflict("Session is already voided".to_string()));
+    }
+    if let Some(user_id) = session.user_id {
+        check_target(&mut tx, &staff, user_id).await?;
+    }
+
+    sqlx::query!(
+        "UPDATE game_sessions SET status = $1, flagged_reason = COALESCE($2, flagged_reason) WHERE id = $3",
+        SESSION_VOIDED,
+        req.reason,
+        session_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let removed = sqlx::query!("DELETE FROM leaderboard_entries WHERE session_id = $1", session_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+    // Only finished sessions counted towards the boards and the profile
+    let mut resubmitted = 0;
+    if session.status == SESSION_FINISHED {
+        if let Some(user_id) = session.user_id {
+            resubmitted = ScoringService::rebuild_user(&mut tx, user_id).await?;
+            rollback_profile(&mut tx, &session, user_id).await?;
+        }
+    }
+
+    audit(
+        &mut *tx,
+        staff.claims.sub,
+        AUDIT_VOID_SESSION,
+        "session",
+        session_id,
+        json!({
+            "reason": req.reason,
+            "previous_status": session.status,
+            "user_id": session.user_id,
+            "entries_removed": removed,
+            "sessions_resubmitted": resubmitted,
+        }),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Take a voided session's result back out of the player's profile stats
+async fn rollback_profile(
+    tx: &mut Transaction<'_, Postgres>,
+    session: &GameSession,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    match session.survived {
+        Some(true) => {
+            sqlx::query!(
+                r#"
+                UPDATE player_profiles
+                SET total_nights_survived = GREATEST(total_nights_survived - 1, 0),
+                    highest_night_completed = COALESCE((
+                        SELECT MAX(night_number) FROM game_sessions
+                        WHERE user_id = $1 AND status = $2 AND survived = true
+                    ), 0),
+                    total_playtime_seconds = GREATEST(total_playtime_seconds - COALESCE($3, 0), 0),
+                    photos_taken = GREATEST(photos_taken - $4, 0),
+                    updated_at = $5
+                WHERE user_id = $1
+                "#,
+                user_id,
+                SESSION_FINISHED,
+                session.time_survived_seconds,
+                session.photos_taken,
+                Utc::now()
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Some(false) => {
+            sqlx::query!(
+                r#"
+                UPDATE player_profiles
+                SET total_deaths = GREATEST(total_deaths - 1, 0),
+                    total_playtime_seconds = GREATEST(total_playtime_seconds - COALESCE($2, 0), 0),
+                    updated_at = $3
+                WHERE user_id = $1
+                "#,
+                user_id,
+                session.time_survived_seconds,
+                Utc::now()
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        None => {}
+    }
+
+    Ok(())
+}
+
+/// Recompute scores and the running season's boards with the current scoring rules.
+/// The job runs in the background, it can take a while on a large season
+pub async fn rescore(
+    State(state): State<AppState>,
+    Admin(claims): Admin,
+) -> Result<StatusCode, AppError> {
+    let season = SeasonService::current(&state.db).await?;
+
+    audit(
+        &state.db,
+        claims.sub,
+        AUDIT_RESCORE,
+        "season",
+        season.id,
+        json!({ "scoring_version": scoring::CURRENT_SCORING_VERSION }),
+    )
+    .await?;
+
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        match ScoringService::rescore(&db).await {
+            Ok(summary) => tracing::info!(
+                "Rescored {} sessions, resubmitted {} to the leaderboards",
+                summary.sessions_rescored,
+                summary.sessions_resubmitted
+            ),
+            Err(e) => tracing::error!("Rescoring failed: {:?}", e),
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Sessions the anti-cheat checks rejected, newest first
+pub async fn list_flagged_sessions(
+    State(state): State<AppState>,
+    _staff: Staff,
+    Query(query): Query<AdminListQuery>,
+) -> Result<Json<FlaggedSessionsResponse>, AppError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = (page - 1) * limit;
+
+    let total = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM game_sessions WHERE status = $1",
+        SESSION_REJECTED
+    )
+    .fetch_one(&state.db)
+    .await?
+    .unwrap_or(0);
+
+    let sessions = sqlx::query_as!(
+        GameSession,
+        r#"
+        SELECT * FROM game_sessions
+        WHERE status = $1
+        ORDER BY ended_at DESC NULLS LAST
+        LIMIT $2 OFFSET $3
+        "#,
+        SESSION_REJECTED,
+        limit as i64,
+        offset as i64
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(FlaggedSessionsResponse { sessions, page, total }))
+}
+
+pub async fn get_audit_log(
+    State(state): State<AppState>,
+    _admin: Admin,
+    Query(query): Query<AdminListQuery>,
+) -> Result<Json<Vec<AuditLogEntry>>, AppError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = (page - 1) * limit;
+
+    let entries = sqlx::query_as!(
+        AuditLogEntry,
+        "SELECT * FROM admin_audit_log ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+        limit as i64,
+        offset as i64
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(entries))
+}
+
+/// Moderators may only act on players, admins on anyone
+async fn check_target(
+    tx: &mut Transaction<'_, Postgres>,
+    staff: &Staff,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let role = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1 FOR UPDATE", user_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if !staff.is_admin() && role != ROLE_PLAYER {
+        return Err(AppError::Forbidden(
+            "Moderators can only moderate players".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn audit(
+    db: impl PgExecutor<'_>,
+    actor_id: Uuid,
+    action: &str,
+    target_type: &str,
+    target_id: Uuid,
+    details: serde_json::Value,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO admin_audit_log (actor_id, action, target_type, target_id, details)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        actor_id,
+        action,
+        target_type,
+        target_id,
+        details
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
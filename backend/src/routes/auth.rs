@@ -1,25 +1,36 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     http::StatusCode,
     Json,
 };
 use chrono::Utc;
 use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
 use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use std::net::SocketAddr;
 use uuid::Uuid;
 
-use crate::{error::AppError, models::*, AppState};
+use crate::{
+    error::AppError,
+    models::*,
+    services::{
+        consume_email_token, create_email_token, generate_opaque_token, hash_token, Email,
+    },
+    validation::ValidatedJson,
+    AppState,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
+    pub jti: Uuid,
     pub exp: i64,
     pub iat: i64,
 }
 
 pub async fn register(
     State(state): State<AppState>,
-    Json(req): Json<RegisterRequest>,
+    ValidatedJson(req): ValidatedJson<RegisterRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
     // Check if email already exists
     let existing = sqlx::query_scalar!(
@@ -68,11 +79,17 @@ pub async fn register(
     .execute(&state.db)
     .await?;
 
-    // Generate token
-    let token = generate_token(user_id, &state.config.jwt_secret, state.config.jwt_expiry_hours)?;
+    // A failed email shouldn't fail the registration, it can be requested again
+    if let Err(e) = send_verification_email(&state, user_id, &req.email).await {
+        tracing::error!("Failed to send verification email to user {}: {:?}", user_id, e);
+    }
+
+    // Generate tokens
+    let (token, refresh_token) = issue_tokens(&state, &state.db, user_id, None).await?;
 
     Ok(Json(AuthResponse {
         token,
+        refresh_token,
         user: UserPublic {
             id: user_id,
             username: req.username,
@@ -82,10 +99,14 @@ pub async fn register(
     }))
 }
 
+// Attempts are rate limited by `LoginRateLimitLayer` in front of this handler
 pub async fn login(
     State(state): State<AppState>,
-    Json(req): Json<LoginRequest>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    ValidatedJson(req): ValidatedJson<LoginRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
+    let ip_address = connect_info.map(|ConnectInfo(addr)| addr.ip().to_string());
+
     // Find user
     let user = sqlx::query_as!(
         User,
@@ -93,14 +114,23 @@ pub async fn login(
         req.email
     )
     .fetch_optional(&state.db)
-    .await?
-    .ok_or_else(|| AppError::Unauthorized("Invalid credentials".to_string()))?;
+    .await?;
+
+    let Some(user) = user else {
+        record_failed_login(&state, &req.email, None, ip_address.as_deref(), "unknown_account").await;
+        return Err(AppError::Unauthorized("Invalid credentials".to_string()));
+    };
 
     // Verify password
     if !verify_password(&req.password, &user.password_hash)? {
+        record_failed_login(&state, &req.email, Some(user.id), ip_address.as_deref(), "wrong_password").await;
         return Err(AppError::Unauthorized("Invalid credentials".to_string()));
     }
 
+    if state.config.require_verified_email && user.email_verified_at.is_none() {
+        return Err(AppError::Forbidden("Email address has not been verified".to_string()));
+    }
+
     // Update last login
     sqlx::query!(
         "UPDATE users SET last_login = $1 WHERE id = $2",
@@ -110,36 +140,116 @@ pub async fn login(
     .execute(&state.db)
     .await?;
 
-    // Generate token
-    let token = generate_token(user.id, &state.config.jwt_secret, state.config.jwt_expiry_hours)?;
+    // Generate tokens
+    let (token, refresh_token) = issue_tokens(&state, &state.db, user.id, None).await?;
 
     Ok(Json(AuthResponse {
         token,
+        refresh_token,
         user: user.into(),
     }))
 }
 
+/// Exchange a refresh token for a new token pair, the old refresh token can't be used again
 pub async fn refresh(
     State(state): State<AppState>,
-    claims: Claims,
+    ValidatedJson(req): ValidatedJson<RefreshRequest>,
 ) -> Result<Json<AuthResponse>, AppError> {
+    let now = Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    let stored = sqlx::query_as!(
+        RefreshToken,
+        "SELECT * FROM refresh_tokens WHERE token_hash = $1 FOR UPDATE",
+        hash_token(&req.refresh_token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::Unauthorized("Invalid refresh token".to_string()))?;
+
+    if stored.revoked_at.is_some() {
+        return Err(AppError::Unauthorized("Refresh token has been revoked".to_string()));
+    }
+
+    if stored.used_at.is_some() {
+        // Someone already rotated this token, assume it was stolen and end the whole login
+        revoke_family(&mut *tx, stored.family_id, now).await?;
+        tx.commit().await?;
+
+        tracing::warn!("Refresh token reuse for user {}, revoked family {}", stored.user_id, stored.family_id);
+        return Err(AppError::Unauthorized("Refresh token reuse detected".to_string()));
+    }
+
+    if stored.expires_at <= now {
+        return Err(AppError::Unauthorized("Refresh token has expired".to_string()));
+    }
+
     let user = sqlx::query_as!(
         User,
         "SELECT * FROM users WHERE id = $1 AND is_active = true",
-        claims.sub
+        stored.user_id
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(&mut *tx)
     .await?
     .ok_or_else(|| AppError::Unauthorized("User not found".to_string()))?;
 
-    let token = generate_token(user.id, &state.config.jwt_secret, state.config.jwt_expiry_hours)?;
+    sqlx::query!(
+        "UPDATE refresh_tokens SET used_at = $1 WHERE id = $2",
+        now,
+        stored.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let (token, refresh_token) = issue_tokens(&state, &mut *tx, user.id, Some(stored.family_id)).await?;
+
+    tx.commit().await?;
 
     Ok(Json(AuthResponse {
         token,
+        refresh_token,
         user: user.into(),
     }))
 }
 
+/// Revoke the login the current access token belongs to
+pub async fn logout(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<StatusCode, AppError> {
+    sqlx::query!(
+        r#"
+        UPDATE refresh_tokens
+        SET revoked_at = $1
+        WHERE revoked_at IS NULL
+          AND family_id IN (SELECT family_id FROM refresh_tokens WHERE access_jti = $2 AND user_id = $3)
+        "#,
+        Utc::now(),
+        claims.jti,
+        claims.sub
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Revoke every login of the current user
+pub async fn logout_all(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<StatusCode, AppError> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+        Utc::now(),
+        claims.sub
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 pub async fn me(
     State(state): State<AppState>,
     claims: Claims,
@@ -156,6 +266,160 @@ pub async fn me(
     Ok(Json(user.into()))
 }
 
+/// Send a new verification email to the current user
+pub async fn request_verification(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<StatusCode, AppError> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE id = $1",
+        claims.sub
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if user.email_verified_at.is_some() {
+        return Err(AppError::BadRequest("Email is already verified".to_string()));
+    }
+
+    send_verification_email(&state, user.id, &user.email).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn verify_email(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<EmailTokenRequest>,
+) -> Result<StatusCode, AppError> {
+    let user_id = consume_email_token(&state.db, &req.token, TOKEN_VERIFY_EMAIL).await?;
+
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, $1) WHERE id = $2",
+        Utc::now(),
+        user_id
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Email a password reset link, the response doesn't reveal whether the account exists
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<PasswordResetRequest>,
+) -> Result<StatusCode, AppError> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT * FROM users WHERE email = $1 AND is_active = true",
+        req.email
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(user) = user {
+        let token = create_email_token(
+            &state.db,
+            user.id,
+            TOKEN_PASSWORD_RESET,
+            chrono::Duration::minutes(PASSWORD_RESET_TTL_MINUTES),
+        )
+        .await?;
+
+        let email = Email::password_reset(&user.email, &user.username, &state.config.app_url, &token);
+        if let Err(e) = state.mailer.send(email).await {
+            tracing::error!("Failed to send password reset email to user {}: {:?}", user.id, e);
+        }
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Set a new password with a reset token and log out every device
+pub async fn reset_password(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<ResetPasswordRequest>,
+) -> Result<StatusCode, AppError> {
+    let password_hash = hash_password(&req.new_password)?;
+    let now = Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    let user_id = consume_email_token(&mut *tx, &req.token, TOKEN_PASSWORD_RESET).await?;
+
+    // Following the emailed link also proves the address
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1,
+            email_verified_at = COALESCE(email_verified_at, $2),
+            updated_at = $2
+        WHERE id = $3
+        "#,
+        password_hash,
+        now,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+        now,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn send_verification_email(state: &AppState, user_id: Uuid, address: &str) -> Result<(), AppError> {
+    let token = create_email_token(
+        &state.db,
+        user_id,
+        TOKEN_VERIFY_EMAIL,
+        chrono::Duration::hours(VERIFY_EMAIL_TTL_HOURS),
+    )
+    .await?;
+
+    state
+        .mailer
+        .send(Email::verify_email(address, &state.config.app_url, &token))
+        .await
+}
+
+/// Keep an audit record of a failed login, a failure to write it doesn't fail the request
+async fn record_failed_login(
+    state: &AppState,
+    email: &str,
+    user_id: Option<Uuid>,
+    ip_address: Option<&str>,
+    reason: &str,
+) {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO failed_logins (id, email, user_id, ip_address, reason, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        email.chars().take(255).collect::<String>(),
+        user_id,
+        ip_address,
+        reason,
+        Utc::now()
+    )
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to record failed login for {}: {:?}", email, e);
+    }
+}
+
 fn hash_password(password: &str) -> Result<String, AppError> {
     use argon2::{
         password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
@@ -185,12 +449,61 @@ fn verify_password(password: &str, hash: &str) -> Result<bool, AppError> {
         .is_ok())
 }
 
-fn generate_token(user_id: Uuid, secret: &str, expiry_hours: i64) -> Result<String, AppError> {
+/// Issue an access token and a refresh token, continuing `family_id` when rotating
+async fn issue_tokens(
+    state: &AppState,
+    db: impl PgExecutor<'_>,
+    user_id: Uuid,
+    family_id: Option<Uuid>,
+) -> Result<(String, String), AppError> {
+    let jti = Uuid::new_v4();
+    let token = generate_token(user_id, jti, &state.config.jwt_secret, state.config.jwt_expiry_hours)?;
+
+    let refresh_token = generate_opaque_token();
+    let now = Utc::now();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, access_jti, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        family_id.unwrap_or_else(Uuid::new_v4),
+        hash_token(&refresh_token),
+        jti,
+        now,
+        now + chrono::Duration::days(state.config.refresh_token_expiry_days)
+    )
+    .execute(db)
+    .await?;
+
+    Ok((token, refresh_token))
+}
+
+async fn revoke_family(
+    db: impl PgExecutor<'_>,
+    family_id: Uuid,
+    now: chrono::DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = $1 WHERE family_id = $2 AND revoked_at IS NULL",
+        now,
+        family_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+fn generate_token(user_id: Uuid, jti: Uuid, secret: &str, expiry_hours: i64) -> Result<String, AppError> {
     let now = Utc::now();
     let exp = now + chrono::Duration::hours(expiry_hours);
 
     let claims = Claims {
         sub: user_id,
+        jti,
         iat: now.timestamp(),
         exp: exp.timestamp(),
     };
@@ -212,3 +525,15 @@ pub fn decode_token(token: &str, secret: &str) -> Result<Claims, AppError> {
     .map(|data| data.claims)
     .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))
 }
+
+/// Whether the access token's login has been logged out
+pub async fn is_token_revoked(db: &PgPool, jti: Uuid) -> Result<bool, AppError> {
+    let revoked = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM refresh_tokens WHERE access_jti = $1 AND revoked_at IS NOT NULL)",
+        jti
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(revoked.unwrap_or(false))
+}
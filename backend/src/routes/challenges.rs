@@ -7,7 +7,7 @@ use rand::{Rng, SeedableRng};
 use rand::rngs::StdRng;
 use uuid::Uuid;
 
-use crate::{error::AppError, models::*, routes::auth::Claims, AppState};
+use crate::{error::AppError, models::*, routes::auth::Claims, services::ChallengeService, AppState};
 
 pub async fn get_today(
     State(state): State<AppState>,
@@ -110,22 +110,39 @@ pub async fn complete(
         return Ok(Json(completion));
     }
 
-    // TODO: Validate completion by checking session_id if provided
+    let session_id = req
+        .session_id
+        .ok_or_else(|| AppError::BadRequest("A session_id is required to complete a challenge".to_string()))?;
+
+    let session = sqlx::query_as!(
+        GameSession,
+        "SELECT * FROM game_sessions WHERE id = $1 AND user_id = $2",
+        session_id,
+        claims.sub
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    ChallengeService::validate_completion(&challenge, &session)?;
 
     let completion_id = Uuid::new_v4();
     let now = Utc::now();
+    let score = Some(session.score);
+    let time_seconds = session.time_survived_seconds;
 
     sqlx::query!(
         r#"
-        INSERT INTO daily_challenge_completions (id, user_id, challenge_id, completed_at, score, time_seconds)
-        VALUES ($1, $2, $3, $4, $5, $6)
+        INSERT INTO daily_challenge_completions (id, user_id, challenge_id, completed_at, score, time_seconds, session_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
         "#,
         completion_id,
         claims.sub,
         challenge_id,
         now,
-        req.score,
-        req.time_seconds
+        score,
+        time_seconds,
+        session_id
     )
     .execute(&state.db)
     .await?;
@@ -135,8 +152,9 @@ pub async fn complete(
         user_id: claims.sub,
         challenge_id,
         completed_at: now,
-        score: req.score,
-        time_seconds: req.time_seconds,
+        score,
+        time_seconds,
+        session_id: Some(session_id),
     }))
 }
 
@@ -192,15 +210,6 @@ async fn get_or_create_challenge(
             "challenge_no_right_door_desc".to_string(),
             100,
         ),
-        "speed_run" => {
-            let target = [300, 360, 420][rng.gen_range(0..3)];
-            (
-                serde_json::json!({ "target_seconds": target }),
-                format!("challenge_speed_{}", target / 60),
-                format!("challenge_speed_{}_desc", target / 60),
-                200 - (target / 3) as i32,
-            )
-        }
         "specific_night" => {
             let night = rng.gen_range(1..=5);
             (
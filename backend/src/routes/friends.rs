@@ -0,0 +1,264 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::*,
+    routes::auth::Claims,
+    services::FriendService,
+    validation::ValidatedJson,
+    AppState,
+};
+
+pub async fn list_friends(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<FriendResponse>>, AppError> {
+    let now = Utc::now();
+    let session_cutoff = now - Duration::minutes(SESSION_TIMEOUT_MINUTES);
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            u.id,
+            u.username,
+            u.display_name,
+            u.avatar_url,
+            u.last_seen_at,
+            f.accepted_at,
+            EXISTS(
+                SELECT 1 FROM game_sessions s
+                WHERE s.user_id = u.id
+                  AND s.status IN ($2, $3)
+                  AND COALESCE(s.last_heartbeat_at, s.started_at) > $4
+            ) AS "in_game!"
+        FROM friendships f
+        JOIN users u ON u.id = CASE WHEN f.requester_id = $1 THEN f.addressee_id ELSE f.requester_id END
+        WHERE (f.requester_id = $1 OR f.addressee_id = $1)
+          AND f.status = $5
+          AND u.is_active = true
+        ORDER BY u.username
+        "#,
+        claims.sub,
+        SESSION_STARTED,
+        SESSION_IN_PROGRESS,
+        session_cutoff,
+        FRIENDSHIP_ACCEPTED
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let friends = rows
+        .into_iter()
+        .map(|row| FriendResponse {
+            presence: FriendService::presence(row.in_game, row.last_seen_at, now).to_string(),
+            last_seen_at: row.last_seen_at,
+            since: row.accepted_at,
+            user: UserPublic {
+                id: row.id,
+                username: row.username,
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            },
+        })
+        .collect();
+
+    Ok(Json(friends))
+}
+
+/// Pending requests sent to and by the user
+pub async fn list_requests(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<FriendRequestsResponse>, AppError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            f.id,
+            f.requester_id,
+            f.created_at,
+            u.id AS user_id,
+            u.username,
+            u.display_name,
+            u.avatar_url
+        FROM friendships f
+        JOIN users u ON u.id = CASE WHEN f.requester_id = $1 THEN f.addressee_id ELSE f.requester_id END
+        WHERE (f.requester_id = $1 OR f.addressee_id = $1)
+          AND f.status = $2
+          AND u.is_active = true
+        ORDER BY f.created_at DESC
+        "#,
+        claims.sub,
+        FRIENDSHIP_PENDING
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut incoming = Vec::new();
+    let mut outgoing = Vec::new();
+    for row in rows {
+        let request = FriendRequestResponse {
+            id: row.id,
+            created_at: row.created_at,
+            user: UserPublic {
+                id: row.user_id,
+                username: row.username,
+                display_name: row.display_name,
+                avatar_url: row.avatar_url,
+            },
+        };
+
+        if row.requester_id == claims.sub {
+            outgoing.push(request);
+        } else {
+            incoming.push(request);
+        }
+    }
+
+    Ok(Json(FriendRequestsResponse { incoming, outgoing }))
+}
+
+/// Send a friend request, or accept theirs if they already sent one
+pub async fn send_request(
+    State(state): State<AppState>,
+    claims: Claims,
+    ValidatedJson(req): ValidatedJson<SendFriendRequest>,
+) -> Result<(StatusCode, Json<Friendship>), AppError> {
+    let addressee_id = sqlx::query_scalar!(
+        "SELECT id FROM users WHERE username = $1 AND is_active = true",
+        req.username.trim()
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    if addressee_id == claims.sub {
+        return Err(AppError::BadRequest("You cannot befriend yourself".to_string()));
+    }
+
+    let existing = sqlx::query_as!(
+        Friendship,
+        r#"
+        SELECT * FROM friendships
+        WHERE (requester_id = $1 AND addressee_id = $2) OR (requester_id = $2 AND addressee_id = $1)
+        "#,
+        claims.sub,
+        addressee_id
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(friendship) = existing {
+        if friendship.status == FRIENDSHIP_ACCEPTED {
+            return Err(AppError::Conflict("You are already friends".to_string()));
+        }
+        if friendship.requester_id == claims.sub {
+            return Err(AppError::Conflict("Friend request already sent".to_string()));
+        }
+
+        let friendship = accept(&state, friendship.id, claims.sub).await?;
+        return Ok((StatusCode::OK, Json(friendship)));
+    }
+
+    let friendship = sqlx::query_as!(
+        Friendship,
+        r#"
+        INSERT INTO friendships (requester_id, addressee_id, status)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (LEAST(requester_id, addressee_id), GREATEST(requester_id, addressee_id)) DO NOTHING
+        RETURNING *
+        "#,
+        claims.sub,
+        addressee_id,
+        FRIENDSHIP_PENDING
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::Conflict("Friend request already exists".to_string()))?;
+
+    Ok((StatusCode::CREATED, Json(friendship)))
+}
+
+pub async fn accept_request(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(request_id): Path<Uuid>,
+) -> Result<Json<Friendship>, AppError> {
+    let friendship = accept(&state, request_id, claims.sub).await?;
+    Ok(Json(friendship))
+}
+
+/// Decline a request sent to the user, or cancel one they sent
+pub async fn decline_request(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(request_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM friendships
+        WHERE id = $1 AND status = $2 AND (requester_id = $3 OR addressee_id = $3)
+        "#,
+        request_id,
+        FRIENDSHIP_PENDING,
+        claims.sub
+    )
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Friend request not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn remove_friend(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(friend_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM friendships
+        WHERE status = $1
+          AND ((requester_id = $2 AND addressee_id = $3) OR (requester_id = $3 AND addressee_id = $2))
+        "#,
+        FRIENDSHIP_ACCEPTED,
+        claims.sub,
+        friend_id
+    )
+    .execute(&state.db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Friend not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Accept a pending request addressed to `user_id`
+async fn accept(state: &AppState, request_id: Uuid, user_id: Uuid) -> Result<Friendship, AppError> {
+    sqlx::query_as!(
+        Friendship,
+        r#"
+        UPDATE friendships SET status = $1, accepted_at = $2
+        WHERE id = $3 AND addressee_id = $4 AND status = $5
+        RETURNING *
+        "#,
+        FRIENDSHIP_ACCEPTED,
+        Utc::now(),
+        request_id,
+        user_id,
+        FRIENDSHIP_PENDING
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Friend request not found".to_string()))
+}
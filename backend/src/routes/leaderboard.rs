@@ -2,30 +2,51 @@ use axum::{
     extract::{Path, Query, State},
     Json,
 };
+use chrono::{NaiveDate, Utc};
+use uuid::Uuid;
 
-use crate::{error::AppError, models::*, routes::auth::Claims, AppState};
+use crate::{
+    error::AppError,
+    middleware::OptionalClaims,
+    models::*,
+    routes::auth::Claims,
+    services::{FriendService, SeasonService},
+    AppState,
+};
+
+/// Every board with how it is ranked
+pub async fn list_leaderboards() -> Json<&'static [LeaderboardDefinition]> {
+    Json(LEADERBOARDS)
+}
 
 pub async fn get_leaderboard(
     State(state): State<AppState>,
+    OptionalClaims(claims): OptionalClaims,
     Path(leaderboard_type): Path<String>,
     Query(query): Query<LeaderboardQuery>,
 ) -> Result<Json<LeaderboardResponse>, AppError> {
     // Validate leaderboard type
-    if !LEADERBOARD_TYPES.contains(&leaderboard_type.as_str()) {
-        return Err(AppError::BadRequest(format!(
-            "Invalid leaderboard type. Valid types: {:?}",
-            LEADERBOARD_TYPES
-        )));
-    }
+    let board = find_board(&leaderboard_type)?;
+    let (timeframe, period_start) = resolve_period(&query)?;
+    let (scope, users) = resolve_scope(&state, &query, claims.as_ref()).await?;
+    let season_id = resolve_season(&state, &query).await?;
 
     let page = query.page.unwrap_or(1).max(1);
-    let limit = query.limit.unwrap_or(50).min(100);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
     let offset = (page - 1) * limit;
 
     // Get total count
     let total_entries = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM leaderboard_entries WHERE leaderboard_type = $1",
-        leaderboard_type
+        r#"
+        SELECT COUNT(*) FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $5
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        season_id
     )
     .fetch_one(&state.db)
     .await?
@@ -35,20 +56,28 @@ pub async fn get_leaderboard(
     let entries = sqlx::query!(
         r#"
         SELECT
+            id,
             user_id,
             username,
             score,
             additional_data,
             achieved_at,
-            RANK() OVER (ORDER BY score DESC) as rank
+            session_id,
+            time_seconds,
+            RANK() OVER (ORDER BY rank_score DESC, achieved_at ASC, time_seconds ASC NULLS LAST) as rank
         FROM leaderboard_entries
-        WHERE leaderboard_type = $1
-        ORDER BY score DESC
-        LIMIT $2 OFFSET $3
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $7
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+        ORDER BY rank_score DESC, achieved_at ASC, time_seconds ASC NULLS LAST, id
+        LIMIT $5 OFFSET $6
         "#,
         leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
         limit as i64,
-        offset as i64
+        offset as i64,
+        season_id
     )
     .fetch_all(&state.db)
     .await?;
@@ -57,11 +86,14 @@ pub async fn get_leaderboard(
         .into_iter()
         .map(|row| LeaderboardEntryWithRank {
             rank: row.rank.unwrap_or(0),
+            id: row.id,
             user_id: row.user_id,
             username: row.username,
             score: row.score,
             additional_data: row.additional_data,
             achieved_at: row.achieved_at,
+            session_id: row.session_id,
+            time_seconds: row.time_seconds,
         })
         .collect();
 
@@ -69,6 +101,11 @@ pub async fn get_leaderboard(
 
     Ok(Json(LeaderboardResponse {
         leaderboard_type,
+        board,
+        season_id,
+        scope: scope.to_string(),
+        timeframe: timeframe.as_str().to_string(),
+        period_start,
         entries: entries_with_rank,
         page,
         total_pages,
@@ -80,7 +117,13 @@ pub async fn get_my_rank(
     State(state): State<AppState>,
     claims: Claims,
     Path(leaderboard_type): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
 ) -> Result<Json<MyRankResponse>, AppError> {
+    find_board(&leaderboard_type)?;
+    let (timeframe, period_start) = resolve_period(&query)?;
+    let (scope, users) = resolve_scope(&state, &query, Some(&claims)).await?;
+    let season_id = resolve_season(&state, &query).await?;
+
     // Get user's rank and score
     let my_entry = sqlx::query!(
         r#"
@@ -88,30 +131,390 @@ pub async fn get_my_rank(
             SELECT
                 user_id,
                 score,
-                RANK() OVER (ORDER BY score DESC) as rank
+                RANK() OVER (ORDER BY rank_score DESC, achieved_at ASC, time_seconds ASC NULLS LAST) as rank
             FROM leaderboard_entries
-            WHERE leaderboard_type = $1
+            WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $6
+              AND ($4::uuid[] IS NULL OR user_id = ANY($4))
         )
-        SELECT rank, score FROM ranked WHERE user_id = $2
+        SELECT rank, score FROM ranked WHERE user_id = $5
         "#,
         leaderboard_type,
-        claims.sub
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        claims.sub,
+        season_id
     )
     .fetch_optional(&state.db)
     .await?;
 
     // Get total players
     let total_players = sqlx::query_scalar!(
-        "SELECT COUNT(*) FROM leaderboard_entries WHERE leaderboard_type = $1",
-        leaderboard_type
+        r#"
+        SELECT COUNT(*) FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $5
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        season_id
     )
     .fetch_one(&state.db)
     .await?
     .unwrap_or(0);
 
     Ok(Json(MyRankResponse {
+        season_id,
+        scope: scope.to_string(),
+        timeframe: timeframe.as_str().to_string(),
+        period_start,
         rank: my_entry.as_ref().and_then(|e| e.rank),
         score: my_entry.map(|e| e.score),
         total_players,
     }))
 }
+
+/// The caller's entry, the `limit` entries either side of it and their percentile.
+/// Walks the rank index outwards from the caller's entry instead of ranking the whole board
+pub async fn get_around_me(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(leaderboard_type): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<AroundMeResponse>, AppError> {
+    find_board(&leaderboard_type)?;
+    let (timeframe, period_start) = resolve_period(&query)?;
+    let (scope, users) = resolve_scope(&state, &query, Some(&claims)).await?;
+    let season_id = resolve_season(&state, &query).await?;
+    let count = query.limit.unwrap_or(5).clamp(1, 25) as i64;
+
+    let total_players = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $5
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        season_id
+    )
+    .fetch_one(&state.db)
+    .await?
+    .unwrap_or(0);
+
+    let mut response = AroundMeResponse {
+        leaderboard_type: leaderboard_type.clone(),
+        season_id,
+        scope: scope.to_string(),
+        timeframe: timeframe.as_str().to_string(),
+        period_start,
+        me: None,
+        above: Vec::new(),
+        below: Vec::new(),
+        total_players,
+        percentile: None,
+    };
+
+    let Some(me) = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT id, user_id, username, leaderboard_type, score, rank_score, additional_data, achieved_at,
+               period, period_start, session_id, time_seconds, season_id
+        FROM leaderboard_entries
+        WHERE user_id = $1 AND leaderboard_type = $2 AND period = $3 AND period_start = $4 AND season_id = $5
+        "#,
+        claims.sub,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        season_id
+    )
+    .fetch_optional(&state.db)
+    .await?
+    else {
+        return Ok(Json(response));
+    };
+
+    // Ties are broken by earlier achieved_at, then shorter time (no time ranks last)
+    let ahead = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $8
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+          AND (
+              rank_score > $5
+              OR (rank_score = $5 AND achieved_at < $6)
+              OR (rank_score = $5 AND achieved_at = $6
+                  AND COALESCE(time_seconds, 2147483647) < COALESCE($7, 2147483647))
+          )
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        me.rank_score,
+        me.achieved_at,
+        me.time_seconds,
+        season_id
+    )
+    .fetch_one(&state.db)
+    .await?
+    .unwrap_or(0);
+
+    let mut above = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT id, user_id, username, leaderboard_type, score, rank_score, additional_data, achieved_at,
+               period, period_start, session_id, time_seconds, season_id
+        FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $9
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+          AND (
+              rank_score > $5
+              OR (rank_score = $5 AND achieved_at < $6)
+              OR (rank_score = $5 AND achieved_at = $6
+                  AND COALESCE(time_seconds, 2147483647) < COALESCE($7, 2147483647))
+          )
+        ORDER BY rank_score ASC, achieved_at DESC, time_seconds DESC NULLS FIRST
+        LIMIT $8
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        me.rank_score,
+        me.achieved_at,
+        me.time_seconds,
+        count,
+        season_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    above.reverse();
+
+    let below = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT id, user_id, username, leaderboard_type, score, rank_score, additional_data, achieved_at,
+               period, period_start, session_id, time_seconds, season_id
+        FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $10
+          AND ($4::uuid[] IS NULL OR user_id = ANY($4))
+          AND id <> $8
+          AND (
+              rank_score < $5
+              OR (rank_score = $5 AND achieved_at > $6)
+              OR (rank_score = $5 AND achieved_at = $6
+                  AND COALESCE(time_seconds, 2147483647) >= COALESCE($7, 2147483647))
+          )
+        ORDER BY rank_score DESC, achieved_at ASC, time_seconds ASC NULLS LAST
+        LIMIT $9
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        period_start,
+        users.as_deref(),
+        me.rank_score,
+        me.achieved_at,
+        me.time_seconds,
+        me.id,
+        count,
+        season_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let rank = ahead + 1;
+    let first_above = rank - above.len() as i64;
+    let behind = total_players - rank;
+
+    response.percentile = Some(if total_players > 1 {
+        behind as f64 * 100.0 / (total_players - 1) as f64
+    } else {
+        100.0
+    });
+    response.above = above
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| entry.with_rank(first_above + i as i64))
+        .collect();
+    response.below = below
+        .into_iter()
+        .enumerate()
+        .map(|(i, entry)| entry.with_rank(rank + 1 + i as i64))
+        .collect();
+    response.me = Some(me.with_rank(rank));
+
+    Ok(Json(response))
+}
+
+/// Earlier periods of a board that have entries, for browsing its history
+pub async fn get_periods(
+    State(state): State<AppState>,
+    Path(leaderboard_type): Path<String>,
+    Query(query): Query<LeaderboardQuery>,
+) -> Result<Json<LeaderboardPeriodsResponse>, AppError> {
+    find_board(&leaderboard_type)?;
+    let (timeframe, _) = resolve_period(&query)?;
+    let season_id = resolve_season(&state, &query).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+
+    let periods = sqlx::query_scalar!(
+        r#"
+        SELECT DISTINCT period_start FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND season_id = $4
+        ORDER BY period_start DESC
+        LIMIT $3
+        "#,
+        leaderboard_type,
+        timeframe.as_str(),
+        limit as i64,
+        season_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(LeaderboardPeriodsResponse {
+        leaderboard_type,
+        season_id,
+        timeframe: timeframe.as_str().to_string(),
+        periods,
+    }))
+}
+
+/// The run behind a leaderboard entry
+pub async fn get_entry(
+    State(state): State<AppState>,
+    Path(entry_id): Path<Uuid>,
+) -> Result<Json<LeaderboardRunResponse>, AppError> {
+    let entry = sqlx::query_as!(
+        LeaderboardEntry,
+        r#"
+        SELECT id, user_id, username, leaderboard_type, score, rank_score, additional_data, achieved_at,
+               period, period_start, session_id, time_seconds, season_id
+        FROM leaderboard_entries WHERE id = $1
+        "#,
+        entry_id
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Leaderboard entry not found".to_string()))?;
+
+    // Entries ranked ahead of this one on its board
+    let rank = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) + 1 FROM leaderboard_entries
+        WHERE leaderboard_type = $1 AND period = $2 AND period_start = $3 AND season_id = $7
+          AND (
+              rank_score > $4
+              OR (rank_score = $4 AND achieved_at < $5)
+              OR (rank_score = $4 AND achieved_at = $5
+                  AND COALESCE(time_seconds, 2147483647) < COALESCE($6, 2147483647))
+          )
+        "#,
+        entry.leaderboard_type,
+        entry.period,
+        entry.period_start,
+        entry.rank_score,
+        entry.achieved_at,
+        entry.time_seconds,
+        entry.season_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let session = match entry.session_id {
+        Some(session_id) => {
+            sqlx::query_as!(
+                GameSession,
+                "SELECT * FROM game_sessions WHERE id = $1",
+                session_id
+            )
+            .fetch_optional(&state.db)
+            .await?
+        }
+        None => None,
+    };
+
+    let has_replay = match entry.session_id {
+        Some(session_id) => sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM session_replays WHERE session_id = $1)",
+            session_id
+        )
+        .fetch_one(&state.db)
+        .await?
+        .unwrap_or(false),
+        None => false,
+    };
+
+    Ok(Json(LeaderboardRunResponse {
+        entry,
+        rank,
+        session,
+        has_replay,
+    }))
+}
+
+fn find_board(leaderboard_type: &str) -> Result<&'static LeaderboardDefinition, AppError> {
+    find_leaderboard(leaderboard_type).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "Invalid leaderboard type. Valid types: {:?}",
+            LEADERBOARDS.iter().map(|board| board.id).collect::<Vec<_>>()
+        ))
+    })
+}
+
+/// The timeframe asked for and the start of the period to show, all time by default
+fn resolve_period(query: &LeaderboardQuery) -> Result<(Timeframe, NaiveDate), AppError> {
+    let timeframe = match query.timeframe.as_deref() {
+        None => Timeframe::All,
+        Some(value) => Timeframe::parse(value).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Invalid timeframe. Valid timeframes: {:?}",
+                TIMEFRAMES.iter().map(|t| t.as_str()).collect::<Vec<_>>()
+            ))
+        })?,
+    };
+
+    let date = query.date.unwrap_or_else(|| Utc::now().date_naive());
+
+    Ok((timeframe, timeframe.period_start(date)))
+}
+
+/// The scope asked for and, for friends boards, the users on it (the user and their friends)
+async fn resolve_scope(
+    state: &AppState,
+    query: &LeaderboardQuery,
+    claims: Option<&Claims>,
+) -> Result<(&'static str, Option<Vec<Uuid>>), AppError> {
+    match query.scope.as_deref() {
+        None | Some(SCOPE_GLOBAL) => Ok((SCOPE_GLOBAL, None)),
+        Some(SCOPE_FRIENDS) => {
+            let claims = claims.ok_or_else(|| {
+                AppError::Unauthorized("Sign in to see the friends leaderboard".to_string())
+            })?;
+
+            let mut users = FriendService::friend_ids(&state.db, claims.sub).await?;
+            users.push(claims.sub);
+
+            Ok((SCOPE_FRIENDS, Some(users)))
+        }
+        Some(_) => Err(AppError::BadRequest(format!(
+            "Invalid scope. Valid scopes: {:?}",
+            [SCOPE_GLOBAL, SCOPE_FRIENDS]
+        ))),
+    }
+}
+
+/// The season asked for, the running one by default
+async fn resolve_season(state: &AppState, query: &LeaderboardQuery) -> Result<Uuid, AppError> {
+    match query.season_id {
+        Some(season_id) => Ok(season_id),
+        None => Ok(SeasonService::current(&state.db).await?.id),
+    }
+}
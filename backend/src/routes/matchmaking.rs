@@ -0,0 +1,46 @@
+// Ranked versus matchmaking - the queue can also be joined through /ws/matchmaking
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::{
+    error::AppError,
+    matchmaking,
+    models::*,
+    routes::auth::Claims,
+    services::{MatchmakingService, RatingService},
+    validation::ValidatedJson,
+    AppState,
+};
+
+pub async fn join_queue(
+    State(state): State<AppState>,
+    claims: Claims,
+    ValidatedJson(req): ValidatedJson<JoinQueueRequest>,
+) -> Result<Json<QueueStatusResponse>, AppError> {
+    let status = MatchmakingService::join(&state.db, claims.sub, &req.role, None).await?;
+    Ok(Json(status))
+}
+
+/// Poll for a match, once matched the response has the room to connect to
+pub async fn get_queue_status(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<QueueStatusResponse>, AppError> {
+    let status = MatchmakingService::status(&state.db, claims.sub).await?;
+    Ok(Json(status))
+}
+
+pub async fn leave_queue(claims: Claims) -> Result<StatusCode, AppError> {
+    if !matchmaking::dequeue(claims.sub) {
+        return Err(AppError::NotFound("You are not in the queue".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_my_ratings(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<PlayerRating>>, AppError> {
+    let ratings = RatingService::ratings(&state.db, claims.sub).await?;
+    Ok(Json(ratings))
+}
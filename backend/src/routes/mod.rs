@@ -4,3 +4,9 @@ pub mod achievements;
 pub mod leaderboard;
 pub mod challenges;
 pub mod multiplayer;
+pub mod pizza;
+pub mod admin;
+pub mod friends;
+pub mod seasons;
+pub mod scoring;
+pub mod matchmaking;
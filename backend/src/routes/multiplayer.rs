@@ -6,7 +6,13 @@ use chrono::Utc;
 use rand::Rng;
 use uuid::Uuid;
 
-use crate::{error::AppError, models::*, routes::auth::Claims, AppState};
+use crate::{
+    error::AppError,
+    models::*,
+    routes::auth::Claims,
+    services::{generate_opaque_token, hash_token},
+    AppState,
+};
 
 pub async fn create_room(
     State(state): State<AppState>,
@@ -19,8 +25,8 @@ pub async fn create_room(
 
     sqlx::query!(
         r#"
-        INSERT INTO multiplayer_rooms (id, room_code, host_user_id, game_mode, max_players, settings, created_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO multiplayer_rooms (id, room_code, host_user_id, game_mode, max_players, current_players, settings, created_at)
+        VALUES ($1, $2, $3, $4, $5, 0, $6, $7)
         "#,
         room_id,
         room_code,
@@ -62,12 +68,13 @@ pub async fn create_room(
         host_user_id: Some(claims.sub),
         game_mode: req.game_mode,
         max_players: req.max_players.unwrap_or(2),
-        current_players: 1,
-        status: "waiting".to_string(),
+        current_players: 0,
+        status: ROOM_WAITING.to_string(),
         settings: req.settings,
         created_at: now,
         started_at: None,
         ended_at: None,
+        ranked: false,
     };
 
     Ok(Json(RoomResponse {
@@ -105,7 +112,7 @@ pub async fn join_room(
     claims: Option<Claims>,
     Path(room_code): Path<String>,
     Json(req): Json<JoinRoomRequest>,
-) -> Result<Json<RoomResponse>, AppError> {
+) -> Result<Json<JoinRoomResponse>, AppError> {
     let room = sqlx::query_as!(
         MultiplayerRoom,
         "SELECT * FROM multiplayer_rooms WHERE room_code = $1",
@@ -115,70 +122,77 @@ pub async fn join_room(
     .await?
     .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
 
-    if room.status != "waiting" {
+    // Check if user already in room
+    if let Some(ref claims) = claims {
+        let existing = sqlx::query!(
+            "SELECT id FROM multiplayer_participants WHERE room_id = $1 AND user_id = $2",
+            room.id,
+            claims.sub
+        )
+        .fetch_optional(&state.db)
+        .await?;
+
+        if let Some(existing) = existing {
+            let Json(room) = get_room(State(state), Path(room_code)).await?;
+            return Ok(Json(JoinRoomResponse {
+                room,
+                participant_id: existing.id,
+                guest_token: None,
+            }));
+        }
+    }
+
+    if room.status != ROOM_WAITING {
         return Err(AppError::BadRequest("Game already started".to_string()));
     }
 
-    if room.current_players >= room.max_players {
+    // current_players tracks connected sockets, capacity is about who has joined
+    let participant_count = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM multiplayer_participants WHERE room_id = $1",
+        room.id
+    )
+    .fetch_one(&state.db)
+    .await?
+    .unwrap_or(0);
+
+    if participant_count >= room.max_players as i64 {
         return Err(AppError::BadRequest("Room is full".to_string()));
     }
 
     let now = Utc::now();
     let participant_id = Uuid::new_v4();
 
-    let (user_id, username, guest_name) = if let Some(ref claims) = claims {
-        let user = sqlx::query!(
-            "SELECT username FROM users WHERE id = $1",
-            claims.sub
-        )
-        .fetch_one(&state.db)
-        .await?;
-        (Some(claims.sub), Some(user.username), None)
+    let (user_id, guest_name, guest_token) = if let Some(ref claims) = claims {
+        (Some(claims.sub), None, None)
     } else {
-        (None, None, req.guest_name.clone())
+        (None, req.guest_name.clone(), Some(generate_opaque_token()))
     };
 
-    // Check if user already in room
-    if let Some(uid) = user_id {
-        let existing = sqlx::query!(
-            "SELECT id FROM multiplayer_participants WHERE room_id = $1 AND user_id = $2",
-            room.id,
-            uid
-        )
-        .fetch_optional(&state.db)
-        .await?;
-
-        if existing.is_some() {
-            return get_room(State(state), Path(room_code)).await;
-        }
-    }
-
     sqlx::query!(
         r#"
-        INSERT INTO multiplayer_participants (id, room_id, user_id, guest_name, joined_at)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO multiplayer_participants (id, room_id, user_id, guest_name, joined_at, guest_token_hash)
+        VALUES ($1, $2, $3, $4, $5, $6)
         "#,
         participant_id,
         room.id,
         user_id,
         guest_name,
-        now
+        now,
+        guest_token.as_deref().map(hash_token)
     )
     .execute(&state.db)
     .await?;
 
-    // Update player count
-    sqlx::query!(
-        "UPDATE multiplayer_rooms SET current_players = current_players + 1 WHERE id = $1",
-        room.id
-    )
-    .execute(&state.db)
-    .await?;
+    let Json(room) = get_room(State(state), Path(room_code)).await?;
 
-    get_room(State(state), Path(room_code)).await
+    Ok(Json(JoinRoomResponse {
+        room,
+        participant_id,
+        guest_token,
+    }))
 }
 
-async fn get_room_participants(
+pub(crate) async fn get_room_participants(
     state: &AppState,
     room_id: Uuid,
 ) -> Result<Vec<ParticipantInfo>, AppError> {
@@ -208,7 +222,7 @@ async fn get_room_participants(
         .collect())
 }
 
-fn generate_room_code() -> String {
+pub(crate) fn generate_room_code() -> String {
     const CHARS: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
     let mut rng = rand::thread_rng();
     (0..6)
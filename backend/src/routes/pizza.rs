@@ -0,0 +1,116 @@
+use axum::{extract::State, Json};
+use chrono::Utc;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::*,
+    routes::auth::Claims,
+    services::{AchievementService, LeaderboardService},
+    AppState,
+};
+
+pub async fn get_catalog() -> Json<&'static [PizzaSliceLocation]> {
+    Json(PIZZA_SLICES)
+}
+
+pub async fn get_mine(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<PizzaSlicesResponse>, AppError> {
+    let slices = sqlx::query_as!(
+        PizzaSliceProgress,
+        "SELECT * FROM pizza_slice_progress WHERE user_id = $1 ORDER BY found_at",
+        claims.sub
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(PizzaSlicesResponse {
+        found: slices.len() as i32,
+        slices,
+        total: PIZZA_SLICE_COUNT,
+    }))
+}
+
+/// Record a found slice, collecting the same slice again is a no-op
+pub async fn collect(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<CollectSliceRequest>,
+) -> Result<Json<PizzaSlicesResponse>, AppError> {
+    if !PIZZA_SLICES.iter().any(|s| s.id == req.slice_id) {
+        return Err(AppError::BadRequest(format!(
+            "Unknown pizza slice '{}'",
+            req.slice_id
+        )));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO pizza_slice_progress (id, user_id, slice_id, found_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, slice_id) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        claims.sub,
+        req.slice_id,
+        Utc::now()
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    if inserted > 0 {
+        let found = sync_profile_count(&mut *tx, claims.sub).await?;
+
+        let user = sqlx::query!(
+            "SELECT username FROM users WHERE id = $1",
+            claims.sub
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        LeaderboardService::submit_score(
+            &mut tx,
+            claims.sub,
+            &user.username,
+            "pizza_collection",
+            found as i64,
+            None,
+            None,
+        )
+        .await?;
+
+        AchievementService::update_progress(&mut tx, claims.sub).await?;
+    }
+    tx.commit().await?;
+
+    get_mine(State(state), claims).await
+}
+
+/// Set the profile's slice count from the distinct slices found
+async fn sync_profile_count(db: impl PgExecutor<'_>, user_id: Uuid) -> Result<i32, AppError> {
+    let found = sqlx::query_scalar!(
+        r#"
+        UPDATE player_profiles
+        SET pizza_slices_collected = (
+                SELECT COUNT(DISTINCT slice_id) FROM pizza_slice_progress WHERE user_id = $1
+            ),
+            updated_at = $2
+        WHERE user_id = $1
+        RETURNING pizza_slices_collected
+        "#,
+        user_id,
+        Utc::now()
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten()
+    .unwrap_or(0);
+
+    Ok(found)
+}
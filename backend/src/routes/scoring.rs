@@ -0,0 +1,17 @@
+use axum::{extract::Path, Json};
+
+use crate::{
+    error::AppError,
+    scoring::{self, ScoringRules},
+};
+
+/// The rules new sessions are scored with, for the client to show the same numbers
+pub async fn get_rules() -> Json<&'static ScoringRules> {
+    Json(scoring::current_rules())
+}
+
+pub async fn get_rules_version(Path(version): Path<i32>) -> Result<Json<&'static ScoringRules>, AppError> {
+    scoring::rules(version)
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("Scoring version {} not found", version)))
+}
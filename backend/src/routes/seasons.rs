@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::*,
+    routes::auth::Claims,
+    services::SeasonService,
+    AppState,
+};
+
+/// Every season, newest first
+pub async fn list_seasons(State(state): State<AppState>) -> Result<Json<Vec<Season>>, AppError> {
+    let seasons = sqlx::query_as!(Season, "SELECT * FROM seasons ORDER BY number DESC")
+        .fetch_all(&state.db)
+        .await?;
+
+    Ok(Json(seasons))
+}
+
+pub async fn get_current(State(state): State<AppState>) -> Result<Json<Season>, AppError> {
+    let season = SeasonService::current(&state.db).await?;
+    Ok(Json(season))
+}
+
+/// Final standings of a board in an archived season
+pub async fn get_standings(
+    State(state): State<AppState>,
+    Path((season_id, leaderboard_type)): Path<(Uuid, String)>,
+    Query(query): Query<SeasonStandingsQuery>,
+) -> Result<Json<SeasonStandingsResponse>, AppError> {
+    if find_leaderboard(&leaderboard_type).is_none() {
+        return Err(AppError::BadRequest(format!(
+            "Invalid leaderboard type. Valid types: {:?}",
+            LEADERBOARDS.iter().map(|board| board.id).collect::<Vec<_>>()
+        )));
+    }
+
+    let season = sqlx::query_as!(Season, "SELECT * FROM seasons WHERE id = $1", season_id)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Season not found".to_string()))?;
+
+    if season.status != SEASON_ARCHIVED {
+        return Err(AppError::BadRequest(
+            "Season is still running, see /api/leaderboard for its live standings".to_string(),
+        ));
+    }
+
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let offset = (page - 1) * limit;
+
+    let total_entries = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM season_standings WHERE season_id = $1 AND leaderboard_type = $2",
+        season_id,
+        leaderboard_type
+    )
+    .fetch_one(&state.db)
+    .await?
+    .unwrap_or(0);
+
+    let standings = sqlx::query_as!(
+        SeasonStanding,
+        r#"
+        SELECT * FROM season_standings
+        WHERE season_id = $1 AND leaderboard_type = $2
+        ORDER BY final_rank, achieved_at
+        LIMIT $3 OFFSET $4
+        "#,
+        season_id,
+        leaderboard_type,
+        limit as i64,
+        offset as i64
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let total_pages = ((total_entries as f64) / (limit as f64)).ceil() as i32;
+
+    Ok(Json(SeasonStandingsResponse {
+        season,
+        leaderboard_type,
+        standings,
+        page,
+        total_pages,
+        total_entries,
+    }))
+}
+
+/// Where the user finished in past seasons
+pub async fn get_my_placements(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<SeasonPlacement>>, AppError> {
+    let placements = sqlx::query_as!(
+        SeasonPlacement,
+        r#"
+        SELECT
+            s.id AS season_id,
+            s.number AS season_number,
+            s.name AS season_name,
+            st.leaderboard_type,
+            st.final_rank,
+            st.score,
+            st.reward_skin
+        FROM season_standings st
+        JOIN seasons s ON s.id = st.season_id
+        WHERE st.user_id = $1
+        ORDER BY s.number DESC, st.final_rank
+        "#,
+        claims.sub
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(placements))
+}
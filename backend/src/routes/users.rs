@@ -5,7 +5,16 @@ use axum::{
 use chrono::Utc;
 use uuid::Uuid;
 
-use crate::{error::AppError, models::*, routes::auth::Claims, AppState};
+use crate::{
+    error::AppError,
+    middleware::OptionalClaims,
+    models::*,
+    routes::auth::Claims,
+    scoring,
+    services::{AchievementService, AntiCheatService, LeaderboardService, ReplayService},
+    validation::ValidatedJson,
+    AppState,
+};
 
 pub async fn get_profile(
     State(state): State<AppState>,
@@ -26,7 +35,7 @@ pub async fn get_profile(
 pub async fn update_profile(
     State(state): State<AppState>,
     claims: Claims,
-    Json(req): Json<UpdateProfileRequest>,
+    ValidatedJson(req): ValidatedJson<UpdateProfileRequest>,
 ) -> Result<Json<PlayerProfile>, AppError> {
     // Update user if display_name or avatar provided
     if req.display_name.is_some() || req.avatar_url.is_some() {
@@ -112,15 +121,16 @@ pub async fn get_public_profile(
 pub async fn create_session(
     State(state): State<AppState>,
     claims: Claims,
-    Json(req): Json<CreateSessionRequest>,
+    ValidatedJson(req): ValidatedJson<CreateSessionRequest>,
 ) -> Result<Json<SessionResponse>, AppError> {
     let session_id = Uuid::new_v4();
     let now = Utc::now();
+    let seed = rand::random::<i64>();
 
     sqlx::query!(
         r#"
-        INSERT INTO game_sessions (id, user_id, session_type, night_number, started_at, easy_mode, custom_difficulty)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO game_sessions (id, user_id, session_type, night_number, started_at, easy_mode, custom_difficulty, starting_power, seed)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
         "#,
         session_id,
         claims.sub,
@@ -129,6 +139,8 @@ pub async fn create_session(
         now,
         req.easy_mode.unwrap_or(false),
         req.custom_difficulty,
+        req.starting_power.unwrap_or(100),
+        seed,
     )
     .execute(&state.db)
     .await?;
@@ -138,28 +150,128 @@ pub async fn create_session(
         session_type: req.session_type,
         night_number: req.night_number,
         started_at: now,
+        seed,
     }))
 }
 
+pub async fn session_heartbeat(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(session_id): Path<Uuid>,
+    ValidatedJson(req): ValidatedJson<SessionHeartbeatRequest>,
+) -> Result<Json<GameSession>, AppError> {
+    let now = Utc::now();
+    let mut tx = state.db.begin().await?;
+
+    let session = sqlx::query_as!(
+        GameSession,
+        "SELECT * FROM game_sessions WHERE id = $1 AND user_id = $2 FOR UPDATE",
+        session_id,
+        claims.sub
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
+
+    if !session.is_open() {
+        return Err(AppError::Conflict(format!("Session is already {}", session.status)));
+    }
+
+    // Progress that could never happen ends the session for review, like a bad final result
+    let reasons = AntiCheatService::check_heartbeat(&session, &req, now);
+    if !reasons.is_empty() {
+        let reason = reasons.join("; ");
+        sqlx::query!(
+            "UPDATE game_sessions SET ended_at = $1, status = $2, flagged_reason = $3 WHERE id = $4",
+            now,
+            SESSION_REJECTED,
+            reason,
+            session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::warn!("Rejected session {} of user {}: {}", session_id, claims.sub, reason);
+        return Err(AppError::BadRequest(format!("Session rejected: {}", reason)));
+    }
+
+    let session = sqlx::query_as!(
+        GameSession,
+        r#"
+        UPDATE game_sessions
+        SET status = $1,
+            last_heartbeat_at = $2,
+            time_survived_seconds = COALESCE($3, time_survived_seconds),
+            final_power = COALESCE($4, final_power),
+            pizza_slices_found = COALESCE($5, pizza_slices_found),
+            photos_taken = COALESCE($6, photos_taken),
+            events = COALESCE($7, events)
+        WHERE id = $8
+        RETURNING *
+        "#,
+        SESSION_IN_PROGRESS,
+        now,
+        req.time_survived_seconds,
+        req.final_power,
+        req.pizza_slices_found,
+        req.photos_taken,
+        req.events,
+        session_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(session))
+}
+
+/// Finalize a session: store the result and update profile stats, exactly once
 pub async fn update_session(
     State(state): State<AppState>,
     claims: Claims,
     Path(session_id): Path<Uuid>,
-    Json(req): Json<UpdateSessionRequest>,
+    ValidatedJson(mut req): ValidatedJson<UpdateSessionRequest>,
 ) -> Result<Json<GameSession>, AppError> {
     let now = Utc::now();
+    let mut tx = state.db.begin().await?;
 
-    // Verify session belongs to user
+    // Verify session belongs to user, locking it against a concurrent finalization
     let session = sqlx::query_as!(
         GameSession,
-        "SELECT * FROM game_sessions WHERE id = $1 AND user_id = $2",
+        "SELECT * FROM game_sessions WHERE id = $1 AND user_id = $2 FOR UPDATE",
         session_id,
         claims.sub
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(&mut *tx)
     .await?
     .ok_or_else(|| AppError::NotFound("Session not found".to_string()))?;
 
+    if !session.is_open() {
+        return Err(AppError::Conflict(format!("Session is already {}", session.status)));
+    }
+
+    // The replay replaces the reported result with the simulated one
+    let mut reasons = ReplayService::verify(&session, &mut req)?;
+
+    let highest_night_completed = sqlx::query_scalar!(
+        "SELECT highest_night_completed FROM player_profiles WHERE user_id = $1",
+        claims.sub
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten()
+    .unwrap_or(0);
+
+    // Impossible results are kept for review instead of counting towards anything
+    reasons.extend(AntiCheatService::check(&session, &req, highest_night_completed, now));
+    let flagged_reason = (!reasons.is_empty()).then(|| reasons.join("; "));
+    let status = if flagged_reason.is_some() {
+        SESSION_REJECTED
+    } else {
+        SESSION_FINISHED
+    };
+
     // Update session
     sqlx::query!(
         r#"
@@ -169,28 +281,53 @@ pub async fn update_session(
             final_power = COALESCE($3, final_power),
             time_survived_seconds = COALESCE($4, time_survived_seconds),
             star_rating = COALESCE($5, star_rating),
-            score = COALESCE($6, score),
-            death_by = COALESCE($7, death_by),
-            power_ups_used = COALESCE($8, power_ups_used),
-            pizza_slices_found = COALESCE($9, pizza_slices_found),
-            photos_taken = COALESCE($10, photos_taken)
-        WHERE id = $11
+            death_by = COALESCE($6, death_by),
+            power_ups_used = COALESCE($7, power_ups_used),
+            pizza_slices_found = COALESCE($8, pizza_slices_found),
+            photos_taken = COALESCE($9, photos_taken),
+            cameras_used = COALESCE($10, cameras_used),
+            power_out = COALESCE($11, power_out),
+            events = COALESCE($12, events),
+            left_door_used = COALESCE($13, left_door_used),
+            right_door_used = COALESCE($14, right_door_used),
+            status = $15,
+            flagged_reason = $16
+        WHERE id = $17
         "#,
         now,
         req.survived,
         req.final_power,
         req.time_survived_seconds,
         req.star_rating,
-        req.score,
         req.death_by,
         req.power_ups_used,
         req.pizza_slices_found,
         req.photos_taken,
+        req.cameras_used,
+        req.power_out,
+        req.events,
+        req.left_door_used,
+        req.right_door_used,
+        status,
+        flagged_reason,
         session_id
     )
-    .execute(&state.db)
+    .execute(&mut *tx)
     .await?;
 
+    // Only verified sessions keep their replay, rejected ones can't have been replayed
+    if flagged_reason.is_none() {
+        if let (Some(replay), Some(seed)) = (&req.replay, session.seed) {
+            ReplayService::save(&mut *tx, session.id, seed, replay).await?;
+        }
+    }
+
+    if let Some(reason) = flagged_reason {
+        tx.commit().await?;
+        tracing::warn!("Rejected session {} of user {}: {}", session_id, claims.sub, reason);
+        return Err(AppError::BadRequest(format!("Session rejected: {}", reason)));
+    }
+
     // Update player profile stats
     if let Some(survived) = req.survived {
         if survived {
@@ -200,19 +337,17 @@ pub async fn update_session(
                 SET total_nights_survived = total_nights_survived + 1,
                     highest_night_completed = GREATEST(highest_night_completed, $1),
                     total_playtime_seconds = total_playtime_seconds + COALESCE($2, 0),
-                    pizza_slices_collected = pizza_slices_collected + COALESCE($3, 0),
-                    photos_taken = photos_taken + COALESCE($4, 0),
-                    updated_at = $5
-                WHERE user_id = $6
+                    photos_taken = photos_taken + COALESCE($3, 0),
+                    updated_at = $4
+                WHERE user_id = $5
                 "#,
                 session.night_number.unwrap_or(0),
                 req.time_survived_seconds.map(|t| t as i64),
-                req.pizza_slices_found,
                 req.photos_taken,
                 now,
                 claims.sub
             )
-            .execute(&state.db)
+            .execute(&mut *tx)
             .await?;
         } else {
             sqlx::query!(
@@ -227,19 +362,109 @@ pub async fn update_session(
                 now,
                 claims.sub
             )
-            .execute(&state.db)
+            .execute(&mut *tx)
             .await?;
         }
     }
 
     // Return updated session
-    let updated = sqlx::query_as!(
+    let mut updated = sqlx::query_as!(
         GameSession,
         "SELECT * FROM game_sessions WHERE id = $1",
         session_id
     )
-    .fetch_one(&state.db)
+    .fetch_one(&mut *tx)
     .await?;
 
+    // Score and stars are computed server-side once the session has a result
+    let rules = scoring::current_rules();
+    let scored = updated
+        .survived
+        .is_some()
+        .then(|| rules.score_session(&updated));
+
+    let mut unlocked = Vec::new();
+    if let Some(scored) = scored {
+        sqlx::query!(
+            r#"
+            UPDATE game_sessions
+            SET score = $1, star_rating = COALESCE($2, star_rating), scoring_version = $3
+            WHERE id = $4
+            "#,
+            scored.score as i32,
+            scored.star_rating,
+            rules.version,
+            session_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        updated.score = scored.score as i32;
+        updated.star_rating = scored.star_rating.or(updated.star_rating);
+        updated.scoring_version = Some(rules.version);
+
+        // Boards and achievements commit together with the result that earned them
+        let user = sqlx::query!(
+            "SELECT username FROM users WHERE id = $1",
+            claims.sub
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        LeaderboardService::submit_session(&mut tx, &updated, &user.username, scored.score).await?;
+        unlocked = AchievementService::update_progress(&mut tx, claims.sub).await?;
+    }
+
+    tx.commit().await?;
+
+    if !unlocked.is_empty() {
+        tracing::info!("User {} unlocked achievements {:?}", claims.sub, unlocked);
+    }
+
     Ok(Json(updated))
 }
+
+/// Replays of finished sessions are public, any other is only shown to its player and staff
+pub async fn get_replay(
+    State(state): State<AppState>,
+    OptionalClaims(claims): OptionalClaims,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<SessionReplay>, AppError> {
+    let session = sqlx::query!(
+        "SELECT user_id, status FROM game_sessions WHERE id = $1",
+        session_id
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Replay not found".to_string()))?;
+
+    if session.status != SESSION_FINISHED {
+        let claims = claims
+            .ok_or_else(|| AppError::Unauthorized("Sign in to view this replay".to_string()))?;
+
+        if session.user_id != Some(claims.sub) {
+            let role = sqlx::query_scalar!(
+                "SELECT role FROM users WHERE id = $1 AND is_active = true",
+                claims.sub
+            )
+            .fetch_optional(&state.db)
+            .await?;
+
+            if !matches!(role.as_deref(), Some(ROLE_MODERATOR | ROLE_ADMIN)) {
+                return Err(AppError::Forbidden(
+                    "Only the player and moderators can view this replay".to_string(),
+                ));
+            }
+        }
+    }
+
+    let replay = sqlx::query_as!(
+        SessionReplay,
+        "SELECT * FROM session_replays WHERE session_id = $1",
+        session_id
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Replay not found".to_string()))?;
+
+    Ok(Json(replay))
+}
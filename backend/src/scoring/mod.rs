@@ -0,0 +1,227 @@
+// Scoring rules shared with game.js
+// Every rule set is kept under its version so stored scores can be explained and
+// recomputed, the client reads the active parameters from /api/scoring/rules
+use serde::Serialize;
+
+use crate::models::GameSession;
+
+/// Version applied to newly finished sessions
+pub const CURRENT_SCORING_VERSION: i32 = 2;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct NightRules {
+    pub points_per_night: i64,
+    pub points_per_power: i64,
+    pub points_per_star: i64,
+    /// Points per second finished under `speed_par_seconds`
+    pub speed_points_per_second: i64,
+    pub speed_par_seconds: i32,
+    /// Score is multiplied by `1 + (night - 1) * night_multiplier_step`
+    pub night_multiplier_step: f64,
+    pub easy_mode_factor: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StarRules {
+    pub base_stars: i32, // for surviving at all
+    pub two_star_power: i32,
+    pub one_star_power: i32,
+    /// A star for finishing within `par_seconds + night * par_seconds_per_night`
+    pub par_seconds: i32,
+    pub par_seconds_per_night: i32,
+    pub no_camera_stars: i32,
+    pub max_stars: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SurvivalRules {
+    pub points_per_second: i64,
+    pub points_per_animatronic_avoided: i64,
+    pub points_per_room_explored: i64,
+    pub points_per_photo: i64,
+    pub points_per_pizza_slice: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ScoringRules {
+    pub version: i32,
+    pub night: NightRules,
+    pub stars: StarRules,
+    pub survival: SurvivalRules,
+}
+
+const STAR_RULES: StarRules = StarRules {
+    base_stars: 1,
+    two_star_power: 50,
+    one_star_power: 25,
+    par_seconds: 420,
+    par_seconds_per_night: 30,
+    no_camera_stars: 1,
+    max_stars: 5,
+};
+
+const SURVIVAL_RULES: SurvivalRules = SurvivalRules {
+    points_per_second: 10,
+    points_per_animatronic_avoided: 50,
+    points_per_room_explored: 25,
+    points_per_photo: 30,
+    points_per_pizza_slice: 100,
+};
+
+pub const SCORING_RULES: &[ScoringRules] = &[
+    // The original server rules
+    ScoringRules {
+        version: 1,
+        night: NightRules {
+            points_per_night: 1000,
+            points_per_power: 5,
+            points_per_star: 100,
+            speed_points_per_second: 1,
+            speed_par_seconds: 600,
+            night_multiplier_step: 0.0,
+            easy_mode_factor: 0.5,
+        },
+        stars: STAR_RULES,
+        survival: SURVIVAL_RULES,
+    },
+    // Matches calculateScore in game.js
+    ScoringRules {
+        version: 2,
+        night: NightRules {
+            points_per_night: 1000,
+            points_per_power: 10,
+            points_per_star: 100,
+            speed_points_per_second: 0,
+            speed_par_seconds: 0,
+            night_multiplier_step: 0.1,
+            easy_mode_factor: 0.5,
+        },
+        stars: STAR_RULES,
+        survival: SURVIVAL_RULES,
+    },
+];
+
+pub fn rules(version: i32) -> Option<&'static ScoringRules> {
+    SCORING_RULES.iter().find(|rules| rules.version == version)
+}
+
+pub fn current_rules() -> &'static ScoringRules {
+    rules(CURRENT_SCORING_VERSION).expect("current scoring version is defined")
+}
+
+/// Score and stars of a finished session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionScore {
+    pub score: i64,
+    pub star_rating: Option<i32>, // only rated for survived nights
+}
+
+impl ScoringRules {
+    pub fn star_rating(&self, final_power: i32, time_seconds: i32, night: i32, used_cameras: bool) -> i32 {
+        let rules = &self.stars;
+        let mut stars = rules.base_stars;
+
+        if final_power >= rules.two_star_power {
+            stars += 2;
+        } else if final_power >= rules.one_star_power {
+            stars += 1;
+        }
+
+        if time_seconds <= rules.par_seconds + night * rules.par_seconds_per_night {
+            stars += 1;
+        }
+
+        if !used_cameras {
+            stars += rules.no_camera_stars;
+        }
+
+        stars.min(rules.max_stars)
+    }
+
+    pub fn night_score(
+        &self,
+        night: i32,
+        survived: bool,
+        final_power: i32,
+        time_seconds: i32,
+        star_rating: i32,
+        easy_mode: bool,
+    ) -> i64 {
+        if !survived {
+            return 0;
+        }
+
+        let rules = &self.night;
+        let points = night as i64 * rules.points_per_night
+            + final_power as i64 * rules.points_per_power
+            + star_rating as i64 * rules.points_per_star
+            + (rules.speed_par_seconds - time_seconds).max(0) as i64 * rules.speed_points_per_second;
+
+        // Multipliers are applied in floating point and floored once, like the client
+        let mut score = points as f64 * (1.0 + (night - 1) as f64 * rules.night_multiplier_step);
+        if easy_mode {
+            score *= rules.easy_mode_factor;
+        }
+
+        score.floor() as i64
+    }
+
+    pub fn survival_score(
+        &self,
+        time_survived_seconds: i32,
+        animatronics_avoided: i32,
+        rooms_explored: i32,
+        photos_taken: i32,
+        pizza_slices: i32,
+    ) -> i64 {
+        let rules = &self.survival;
+
+        time_survived_seconds as i64 * rules.points_per_second
+            + animatronics_avoided as i64 * rules.points_per_animatronic_avoided
+            + rooms_explored as i64 * rules.points_per_room_explored
+            + photos_taken as i64 * rules.points_per_photo
+            + pizza_slices as i64 * rules.points_per_pizza_slice
+    }
+
+    /// Score a finished session from its stored fields
+    pub fn score_session(&self, session: &GameSession) -> SessionScore {
+        match session.session_type.as_str() {
+            "night" => {
+                let survived = session.survived.unwrap_or(false);
+                let night = session.night_number.unwrap_or(0);
+                let final_power = session.final_power.unwrap_or(0);
+                let time_seconds = session.time_survived_seconds.unwrap_or(0);
+                let star_rating = survived.then(|| {
+                    self.star_rating(final_power, time_seconds, night, session.cameras_used.unwrap_or(true))
+                });
+
+                SessionScore {
+                    score: self.night_score(
+                        night,
+                        survived,
+                        final_power,
+                        time_seconds,
+                        star_rating.unwrap_or(0),
+                        session.easy_mode,
+                    ),
+                    star_rating,
+                }
+            }
+            // Avoided animatronics and explored rooms are not tracked per session yet
+            "survival" => SessionScore {
+                score: self.survival_score(
+                    session.time_survived_seconds.unwrap_or(0),
+                    0,
+                    0,
+                    session.photos_taken,
+                    session.pizza_slices_found,
+                ),
+                star_rating: None,
+            },
+            _ => SessionScore {
+                score: 0,
+                star_rating: None,
+            },
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{error::AppError, models::*};
+
+/// A single requirement parsed from `achievements.requirements`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    /// Survive this night (or a later one)
+    Night(i32),
+    /// Custom night AI levels for freddy, bonnie, chica, foxy
+    CustomLevels(Vec<i64>),
+    /// Survive a night with at least this much power left
+    MinPower(i32),
+    /// Survive a night after the power ran out
+    SurvivedPowerOut,
+    /// Survive a night without opening the cameras
+    NoCameras,
+    /// Survive a night in less than this many seconds
+    TimeUnder(i32),
+    /// Last this many seconds in survival mode
+    SurvivalTime(i32),
+    /// Collect this many pizza slices in total
+    PizzaCount(i32),
+    /// Take this many photos in total
+    Photos(i32),
+    PhotoGoldenFreddy,
+    GoldenFreddy,
+    /// Complete this many different minigames
+    MinigamesCompleted(i32),
+}
+
+impl Requirement {
+    /// Parse a requirements object, failing on unknown keys or malformed values
+    pub fn parse_all(requirements: &serde_json::Value) -> Result<Vec<Requirement>, String> {
+        let object = requirements
+            .as_object()
+            .ok_or_else(|| "requirements must be a JSON object".to_string())?;
+
+        object
+            .iter()
+            .map(|(key, value)| {
+                let int = || {
+                    value
+                        .as_i64()
+                        .map(|v| v as i32)
+                        .ok_or_else(|| format!("'{}' must be an integer", key))
+                };
+                let flag = |requirement: Requirement| match value.as_bool() {
+                    Some(true) => Ok(requirement),
+                    _ => Err(format!("'{}' must be true", key)),
+                };
+
+                match key.as_str() {
+                    "night" => int().map(Requirement::Night),
+                    "custom" => value
+                        .as_array()
+                        .and_then(|levels| levels.iter().map(|l| l.as_i64()).collect::<Option<Vec<_>>>())
+                        .filter(|levels| levels.len() == 4)
+                        .map(Requirement::CustomLevels)
+                        .ok_or_else(|| "'custom' must be an array of 4 AI levels".to_string()),
+                    "min_power" => int().map(Requirement::MinPower),
+                    "survived_powerout" => flag(Requirement::SurvivedPowerOut),
+                    "no_cameras" => flag(Requirement::NoCameras),
+                    "time_under" => int().map(Requirement::TimeUnder),
+                    "survival_time" => int().map(Requirement::SurvivalTime),
+                    "pizza_count" => int().map(Requirement::PizzaCount),
+                    "photos" => int().map(Requirement::Photos),
+                    "photo_golden_freddy" => flag(Requirement::PhotoGoldenFreddy),
+                    "golden_freddy" => flag(Requirement::GoldenFreddy),
+                    "minigames_completed" => int().map(Requirement::MinigamesCompleted),
+                    _ => Err(format!("unknown requirement '{}'", key)),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the requirement has to be met within a single session
+    fn is_session_scoped(&self) -> bool {
+        matches!(
+            self,
+            Requirement::Night(_)
+                | Requirement::CustomLevels(_)
+                | Requirement::MinPower(_)
+                | Requirement::SurvivedPowerOut
+                | Requirement::NoCameras
+                | Requirement::TimeUnder(_)
+                | Requirement::SurvivalTime(_)
+        )
+    }
+
+    fn met_by_session(&self, session: &GameSession) -> bool {
+        let survived_night = session.session_type == "night" && session.survived == Some(true);
+
+        match self {
+            Requirement::Night(night) => survived_night && session.night_number.unwrap_or(0) >= *night,
+            Requirement::CustomLevels(levels) => {
+                let Some(custom) = &session.custom_difficulty else {
+                    return false;
+                };
+                survived_night
+                    && ["freddy", "bonnie", "chica", "foxy"]
+                        .iter()
+                        .zip(levels)
+                        .all(|(name, level)| custom[*name].as_i64().unwrap_or(0) >= *level)
+            }
+            Requirement::MinPower(power) => survived_night && session.final_power.unwrap_or(0) >= *power,
+            Requirement::SurvivedPowerOut => survived_night && session.power_out,
+            Requirement::NoCameras => survived_night && session.cameras_used == Some(false),
+            Requirement::TimeUnder(seconds) => {
+                survived_night && session.time_survived_seconds.map_or(false, |t| t < *seconds)
+            }
+            Requirement::SurvivalTime(seconds) => {
+                session.session_type == "survival" && session.time_survived_seconds.unwrap_or(0) >= *seconds
+            }
+            _ => false,
+        }
+    }
+
+    fn met_by_history(&self, ctx: &RequirementContext) -> bool {
+        match self {
+            Requirement::PizzaCount(count) => ctx.profile.pizza_slices_collected >= *count,
+            Requirement::Photos(count) => ctx.profile.photos_taken >= *count,
+            Requirement::PhotoGoldenFreddy => ctx.has_event(EVENT_GOLDEN_FREDDY_PHOTO),
+            Requirement::GoldenFreddy => ctx.has_event(EVENT_GOLDEN_FREDDY_SEEN),
+            Requirement::MinigamesCompleted(count) => ctx.minigames_completed() >= *count as usize,
+            _ => false,
+        }
+    }
+
+    fn met_alone(&self, ctx: &RequirementContext) -> bool {
+        if self.is_session_scoped() {
+            ctx.sessions.iter().any(|s| self.met_by_session(s))
+        } else {
+            self.met_by_history(ctx)
+        }
+    }
+
+    /// Current and target value for this requirement, current capped at target
+    fn progress(&self, ctx: &RequirementContext) -> (i64, i64) {
+        let best = |value: fn(&GameSession) -> Option<i32>| {
+            ctx.sessions.iter().filter_map(value).max().unwrap_or(0) as i64
+        };
+
+        let (current, target) = match self {
+            Requirement::Night(night) => (
+                best(|s| {
+                    (s.session_type == "night" && s.survived == Some(true))
+                        .then_some(s.night_number)
+                        .flatten()
+                }),
+                *night as i64,
+            ),
+            Requirement::MinPower(power) => (
+                best(|s| {
+                    (s.session_type == "night" && s.survived == Some(true))
+                        .then_some(s.final_power)
+                        .flatten()
+                }),
+                *power as i64,
+            ),
+            Requirement::SurvivalTime(seconds) => (
+                best(|s| (s.session_type == "survival").then_some(s.time_survived_seconds).flatten()),
+                *seconds as i64,
+            ),
+            Requirement::PizzaCount(count) => (ctx.profile.pizza_slices_collected as i64, *count as i64),
+            Requirement::Photos(count) => (ctx.profile.photos_taken as i64, *count as i64),
+            Requirement::MinigamesCompleted(count) => (ctx.minigames_completed() as i64, *count as i64),
+            _ => (self.met_alone(ctx) as i64, 1),
+        };
+
+        (current.clamp(0, target), target)
+    }
+
+    /// Human readable description used in rejection messages
+    pub fn describe(&self) -> String {
+        match self {
+            Requirement::Night(night) => format!("survive night {}", night),
+            Requirement::CustomLevels(levels) => format!("survive a custom night at AI levels {:?}", levels),
+            Requirement::MinPower(power) => format!("survive a night with at least {}% power", power),
+            Requirement::SurvivedPowerOut => "survive a night after a power outage".to_string(),
+            Requirement::NoCameras => "survive a night without using the cameras".to_string(),
+            Requirement::TimeUnder(seconds) => format!("survive a night in under {} seconds", seconds),
+            Requirement::SurvivalTime(seconds) => format!("last {} seconds in survival mode", seconds),
+            Requirement::PizzaCount(count) => format!("collect {} pizza slices", count),
+            Requirement::Photos(count) => format!("take {} photos", count),
+            Requirement::PhotoGoldenFreddy => "photograph Golden Freddy".to_string(),
+            Requirement::GoldenFreddy => "encounter Golden Freddy".to_string(),
+            Requirement::MinigamesCompleted(count) => format!("complete {} minigames", count),
+        }
+    }
+}
+
+/// Everything the evaluator knows about a player
+pub struct RequirementContext {
+    pub profile: PlayerProfile,
+    pub sessions: Vec<GameSession>,
+}
+
+impl RequirementContext {
+    pub async fn load(db: &mut PgConnection, user_id: Uuid) -> Result<Self, AppError> {
+        let profile = sqlx::query_as!(
+            PlayerProfile,
+            "SELECT * FROM player_profiles WHERE user_id = $1",
+            user_id
+        )
+        .fetch_optional(&mut *db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Profile not found".to_string()))?;
+
+        let sessions = sqlx::query_as!(
+            GameSession,
+            "SELECT * FROM game_sessions WHERE user_id = $1 AND status = $2",
+            user_id,
+            SESSION_FINISHED
+        )
+        .fetch_all(&mut *db)
+        .await?;
+
+        Ok(RequirementContext { profile, sessions })
+    }
+
+    fn events(&self) -> impl Iterator<Item = &str> {
+        self.sessions
+            .iter()
+            .filter_map(|s| s.events.as_array())
+            .flatten()
+            .filter_map(|e| e.as_str())
+    }
+
+    fn has_event(&self, event: &str) -> bool {
+        self.events().any(|e| e == event)
+    }
+
+    fn minigames_completed(&self) -> usize {
+        let mut minigames: Vec<&str> = self
+            .events()
+            .filter_map(|e| e.strip_prefix(EVENT_MINIGAME_PREFIX))
+            .collect();
+        minigames.sort_unstable();
+        minigames.dedup();
+        minigames.len()
+    }
+
+    /// Return the requirements that are not met, empty if the achievement is earned
+    pub fn unmet<'r>(&self, requirements: &'r [Requirement]) -> Vec<&'r Requirement> {
+        let (session_scoped, history): (Vec<_>, Vec<_>) =
+            requirements.iter().partition(|r| r.is_session_scoped());
+
+        let mut unmet: Vec<&Requirement> = history
+            .into_iter()
+            .filter(|r| !r.met_by_history(self))
+            .collect();
+
+        // Session scoped requirements must all hold in the same session
+        let any_session_matches = session_scoped.is_empty()
+            || self
+                .sessions
+                .iter()
+                .any(|s| session_scoped.iter().all(|r| r.met_by_session(s)));
+
+        if !any_session_matches {
+            unmet.extend(session_scoped);
+        }
+
+        unmet
+    }
+
+    /// Progress towards an achievement. Single requirements report their own
+    /// values, combined ones report how many of their requirements are met
+    pub fn progress(&self, requirements: &[Requirement]) -> AchievementProgress {
+        let earned = self.unmet(requirements).is_empty();
+
+        let (current, target) = match requirements {
+            [] => (earned as i64, 1),
+            [requirement] => requirement.progress(self),
+            _ => (
+                requirements.iter().filter(|r| r.met_alone(self)).count() as i64,
+                requirements.len() as i64,
+            ),
+        };
+
+        // Requirements met separately but not within the same session are not done yet
+        let current = if earned { target } else { current.min(target - 1) };
+
+        AchievementProgress { current, target }
+    }
+}
+
+pub struct AchievementService;
+
+impl AchievementService {
+    /// Check that every achievement in the catalog has requirements we can evaluate
+    pub async fn validate_catalog(db: &PgPool) -> anyhow::Result<()> {
+        let achievements = sqlx::query!("SELECT id, requirements FROM achievements")
+            .fetch_all(db)
+            .await?;
+
+        for achievement in achievements {
+            Requirement::parse_all(&achievement.requirements).map_err(|e| {
+                anyhow::anyhow!("Achievement '{}' has invalid requirements: {}", achievement.id, e)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn parse_requirements(achievement: &Achievement) -> Result<Vec<Requirement>, AppError> {
+        Requirement::parse_all(&achievement.requirements).map_err(|e| {
+            AppError::Internal(format!("Achievement '{}' has invalid requirements: {}", achievement.id, e))
+        })
+    }
+
+    /// Recompute progress on every locked achievement, unlocking the ones now met
+    /// Returns the ids of newly unlocked achievements. Runs in the caller's transaction
+    pub async fn update_progress(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, AppError> {
+        let locked = sqlx::query_as!(
+            Achievement,
+            r#"
+            SELECT a.* FROM achievements a
+            WHERE NOT EXISTS (
+                SELECT 1 FROM player_achievements pa
+                WHERE pa.achievement_id = a.id AND pa.user_id = $1 AND pa.unlocked_at IS NOT NULL
+            )
+            "#,
+            user_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        if locked.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let ctx = RequirementContext::load(tx, user_id).await?;
+        let now = Utc::now();
+        let mut unlocked = Vec::new();
+
+        for achievement in locked {
+            let requirements = Self::parse_requirements(&achievement)?;
+            let progress = ctx.progress(&requirements);
+            let earned = ctx.unmet(&requirements).is_empty();
+
+            let unlocked_at = earned.then_some(now);
+            Self::save_progress(&mut **tx, user_id, &achievement.id, &progress, unlocked_at).await?;
+
+            if earned {
+                unlocked.push(achievement.id);
+            }
+        }
+
+        Ok(unlocked)
+    }
+
+    /// Insert or update a player's progress row. Rows that are already unlocked are left alone
+    pub async fn save_progress(
+        db: impl PgExecutor<'_>,
+        user_id: Uuid,
+        achievement_id: &str,
+        progress: &AchievementProgress,
+        unlocked_at: Option<DateTime<Utc>>,
+    ) -> Result<Option<PlayerAchievement>, AppError> {
+        let progress = serde_json::to_value(progress)
+            .map_err(|_| AppError::Internal("Failed to serialize progress".to_string()))?;
+
+        let player_achievement = sqlx::query_as!(
+            PlayerAchievement,
+            r#"
+            INSERT INTO player_achievements (id, user_id, achievement_id, unlocked_at, progress)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (user_id, achievement_id) DO UPDATE SET
+                unlocked_at = EXCLUDED.unlocked_at,
+                progress = EXCLUDED.progress
+            WHERE player_achievements.unlocked_at IS NULL
+            RETURNING *
+            "#,
+            Uuid::new_v4(),
+            user_id,
+            achievement_id,
+            unlocked_at,
+            progress,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(player_achievement)
+    }
+}
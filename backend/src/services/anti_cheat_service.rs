@@ -0,0 +1,119 @@
+// Anti-cheat service - plausibility checks on reported session results
+use chrono::{DateTime, Utc};
+
+use crate::{models::*, simulation::max_power_after};
+
+/// Allowance for request latency between the client's clock and ours
+const CLOCK_SLACK_SECONDS: i64 = 5;
+
+/// Allowance for the client rounding power differently
+const POWER_SLACK: i32 = 1;
+
+pub struct AntiCheatService;
+
+/// A session's result as it would be stored, the reported values over the stored ones
+struct Reported {
+    time_survived_seconds: Option<i32>,
+    final_power: Option<i32>,
+    pizza_slices_found: i32,
+    photos_taken: i32,
+}
+
+impl AntiCheatService {
+    /// Every reason the reported result is impossible, empty when it is plausible
+    pub fn check(
+        session: &GameSession,
+        req: &UpdateSessionRequest,
+        highest_night_completed: i32,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let reported = Reported {
+            time_survived_seconds: req.time_survived_seconds.or(session.time_survived_seconds),
+            final_power: req.final_power.or(session.final_power),
+            pizza_slices_found: req.pizza_slices_found.unwrap_or(session.pizza_slices_found),
+            photos_taken: req.photos_taken.unwrap_or(session.photos_taken),
+        };
+        let mut reasons = Self::check_reported(session, &reported, now);
+
+        if let Some(stars) = req.star_rating {
+            if !(1..=5).contains(&stars) {
+                reasons.push(format!("star_rating {} is outside 1-5", stars));
+            }
+        }
+
+        if session.session_type == "night" {
+            if let Some(night) = session.night_number {
+                if night > highest_night_completed + 1 {
+                    reasons.push(format!(
+                        "night {} is not unlocked, highest completed is {}",
+                        night, highest_night_completed
+                    ));
+                }
+            }
+        }
+
+        reasons
+    }
+
+    /// The same checks on the progress a running session reports
+    pub fn check_heartbeat(
+        session: &GameSession,
+        req: &SessionHeartbeatRequest,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let reported = Reported {
+            time_survived_seconds: req.time_survived_seconds.or(session.time_survived_seconds),
+            final_power: req.final_power.or(session.final_power),
+            pizza_slices_found: req.pizza_slices_found.unwrap_or(session.pizza_slices_found),
+            photos_taken: req.photos_taken.unwrap_or(session.photos_taken),
+        };
+
+        Self::check_reported(session, &reported, now)
+    }
+
+    fn check_reported(session: &GameSession, reported: &Reported, now: DateTime<Utc>) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        let time_survived = reported.time_survived_seconds;
+        let elapsed = (now - session.started_at).num_seconds();
+
+        if let Some(time) = time_survived {
+            if time < 0 {
+                reasons.push("time_survived_seconds is negative".to_string());
+            } else if time as i64 > elapsed + CLOCK_SLACK_SECONDS {
+                reasons.push(format!(
+                    "time_survived_seconds {} is longer than the {}s since the session started",
+                    time, elapsed
+                ));
+            }
+        }
+
+        if let Some(power) = reported.final_power {
+            if !(0..=100).contains(&power) {
+                reasons.push(format!("final_power {} is outside 0-100", power));
+            } else if session.session_type == "night" {
+                let max_power = max_power_after(session.starting_power, time_survived.unwrap_or(0));
+                if power > max_power + POWER_SLACK {
+                    reasons.push(format!(
+                        "final_power {} is higher than the {} achievable on this night",
+                        power, max_power
+                    ));
+                }
+            }
+        }
+
+        let slices = reported.pizza_slices_found;
+        if !(0..=PIZZA_SLICE_COUNT).contains(&slices) {
+            reasons.push(format!(
+                "pizza_slices_found {} is outside 0-{}",
+                slices, PIZZA_SLICE_COUNT
+            ));
+        }
+
+        if reported.photos_taken < 0 {
+            reasons.push("photos_taken is negative".to_string());
+        }
+
+        reasons
+    }
+}
@@ -1,2 +1,83 @@
 // Auth service - helper functions for authentication
 // Most auth logic is in routes/auth.rs for now
+use chrono::{Duration, Utc};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// Generate a random opaque token to hand out to a client
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Hash an opaque token for storage, only the hash is kept in the database
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Create a single-use emailed token for `purpose`, invalidating earlier unused ones
+pub async fn create_email_token(
+    db: &PgPool,
+    user_id: Uuid,
+    purpose: &str,
+    ttl: Duration,
+) -> Result<String, AppError> {
+    let now = Utc::now();
+
+    sqlx::query!(
+        "UPDATE email_tokens SET used_at = $1 WHERE user_id = $2 AND purpose = $3 AND used_at IS NULL",
+        now,
+        user_id,
+        purpose
+    )
+    .execute(db)
+    .await?;
+
+    let token = generate_opaque_token();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_tokens (id, user_id, purpose, token_hash, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        purpose,
+        hash_token(&token),
+        now,
+        now + ttl
+    )
+    .execute(db)
+    .await?;
+
+    Ok(token)
+}
+
+/// Use up an emailed token, returning the user it was issued to
+pub async fn consume_email_token(
+    db: impl PgExecutor<'_>,
+    token: &str,
+    purpose: &str,
+) -> Result<Uuid, AppError> {
+    let now = Utc::now();
+
+    sqlx::query_scalar!(
+        r#"
+        UPDATE email_tokens
+        SET used_at = $1
+        WHERE token_hash = $2 AND purpose = $3 AND used_at IS NULL AND expires_at > $1
+        RETURNING user_id
+        "#,
+        now,
+        hash_token(token),
+        purpose
+    )
+    .fetch_optional(db)
+    .await?
+    .ok_or_else(|| AppError::BadRequest("Invalid or expired token".to_string()))
+}
@@ -1,2 +1,105 @@
 // Challenge service - helper functions for daily challenges
-// Most logic is in routes/challenges.rs for now
+// Challenge generation is in routes/challenges.rs, completion validation lives here
+use crate::{error::AppError, models::*};
+
+pub struct ChallengeService;
+
+impl ChallengeService {
+    /// Check that a finished session satisfies the challenge it is submitted for
+    pub fn validate_completion(
+        challenge: &DailyChallenge,
+        session: &GameSession,
+    ) -> Result<(), AppError> {
+        if session.status != SESSION_FINISHED {
+            return Err(AppError::BadRequest("Session has not finished yet".to_string()));
+        }
+
+        if session.started_at.date_naive() != challenge.challenge_date {
+            return Err(AppError::BadRequest(
+                "Session was not played on the challenge date".to_string(),
+            ));
+        }
+
+        let survived_night = session.session_type == "night" && session.survived == Some(true);
+
+        let (passed, reason) = match challenge.challenge_type.as_str() {
+            "power_limit" => {
+                let limit = Self::int_param(challenge, "power_limit")?;
+                (
+                    survived_night && session.starting_power as i64 <= limit,
+                    format!("Survive a night starting with at most {}% power", limit),
+                )
+            }
+            "no_cameras" => (
+                survived_night && session.cameras_used == Some(false),
+                "Survive a night without using the cameras".to_string(),
+            ),
+            "no_left_door" => (
+                survived_night && session.left_door_used == Some(false),
+                "Survive a night without using the left door".to_string(),
+            ),
+            "no_right_door" => (
+                survived_night && session.right_door_used == Some(false),
+                "Survive a night without using the right door".to_string(),
+            ),
+            // No longer generated, simulated nights always last the full six hours.
+            // Challenges created before that only ask for the night to be survived
+            "speed_run" => (survived_night, "Survive a night".to_string()),
+            "specific_night" => {
+                let night = Self::int_param(challenge, "night")?;
+                (
+                    survived_night && session.night_number.map(|n| n as i64) == Some(night),
+                    format!("Survive night {}", night),
+                )
+            }
+            "collect_pizza" => {
+                let count = Self::int_param(challenge, "pizza_count")?;
+                (
+                    session.pizza_slices_found as i64 >= count,
+                    format!("Find {} pizza slices in one session", count),
+                )
+            }
+            "photo_challenge" => {
+                let target = challenge.parameters["target"]
+                    .as_str()
+                    .ok_or_else(|| Self::bad_parameters(challenge, "target"))?;
+                let event = format!("{}{}", EVENT_PHOTO_PREFIX, target);
+                (
+                    session
+                        .events
+                        .as_array()
+                        .map_or(false, |events| events.iter().any(|e| e.as_str() == Some(&event))),
+                    format!("Take a photo of {}", target),
+                )
+            }
+            other => {
+                return Err(AppError::Internal(format!(
+                    "No validator for challenge type '{}'",
+                    other
+                )))
+            }
+        };
+
+        if !passed {
+            return Err(AppError::BadRequest(format!(
+                "Session does not meet the challenge: {}",
+                reason
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn int_param(challenge: &DailyChallenge, key: &str) -> Result<i64, AppError> {
+        challenge.parameters[key]
+            .as_i64()
+            .ok_or_else(|| Self::bad_parameters(challenge, key))
+    }
+
+    fn bad_parameters(challenge: &DailyChallenge, key: &str) -> AppError {
+        AppError::Internal(format!(
+            "Challenge {} is missing parameter '{}'",
+            challenge.id, key
+        ))
+    }
+}
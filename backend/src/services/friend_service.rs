@@ -0,0 +1,53 @@
+// Friend service - friend lookups shared by the friends list and the friends leaderboards
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{error::AppError, models::*};
+
+pub struct FriendService;
+
+impl FriendService {
+    /// Ids of the user's accepted friends
+    pub async fn friend_ids(db: &PgPool, user_id: Uuid) -> Result<Vec<Uuid>, AppError> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT CASE WHEN requester_id = $1 THEN addressee_id ELSE requester_id END AS "id!"
+            FROM friendships
+            WHERE (requester_id = $1 OR addressee_id = $1) AND status = $2
+            "#,
+            user_id,
+            FRIENDSHIP_ACCEPTED
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Presence shown for a friend
+    pub fn presence(in_game: bool, last_seen_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> &'static str {
+        if in_game {
+            PRESENCE_IN_GAME
+        } else if last_seen_at.map_or(false, |seen| now - seen < Duration::minutes(ONLINE_WINDOW_MINUTES)) {
+            PRESENCE_ONLINE
+        } else {
+            PRESENCE_OFFLINE
+        }
+    }
+
+    /// Record that the user is active, at most once a minute
+    pub async fn touch_last_seen(db: &PgPool, user_id: Uuid) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE users SET last_seen_at = NOW()
+            WHERE id = $1 AND (last_seen_at IS NULL OR last_seen_at < NOW() - INTERVAL '1 minute')
+            "#,
+            user_id
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
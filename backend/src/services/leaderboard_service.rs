@@ -1,110 +1,152 @@
-use sqlx::PgPool;
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 use chrono::Utc;
 
-use crate::error::AppError;
+use crate::{error::AppError, models::*, services::SeasonService};
 
 pub struct LeaderboardService;
 
 impl LeaderboardService {
-    /// Submit a score to the leaderboard
-    /// Updates existing entry if score is higher, or creates new entry
-    pub async fn submit_score(
-        db: &PgPool,
-        user_id: Uuid,
+    /// Submit a finished session to every leaderboard it qualifies for
+    pub async fn submit_session(
+        tx: &mut Transaction<'_, Postgres>,
+        session: &GameSession,
         username: &str,
-        leaderboard_type: &str,
         score: i64,
-        additional_data: Option<serde_json::Value>,
     ) -> Result<(), AppError> {
-        let now = Utc::now();
-
-        // Upsert: insert or update if score is higher
-        sqlx::query!(
-            r#"
-            INSERT INTO leaderboard_entries (id, user_id, username, leaderboard_type, score, additional_data, achieved_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            ON CONFLICT (user_id, leaderboard_type)
-            DO UPDATE SET
-                score = GREATEST(leaderboard_entries.score, EXCLUDED.score),
-                additional_data = CASE
-                    WHEN EXCLUDED.score > leaderboard_entries.score THEN EXCLUDED.additional_data
-                    ELSE leaderboard_entries.additional_data
-                END,
-                achieved_at = CASE
-                    WHEN EXCLUDED.score > leaderboard_entries.score THEN EXCLUDED.achieved_at
-                    ELSE leaderboard_entries.achieved_at
-                END
-            "#,
-            Uuid::new_v4(),
-            user_id,
-            username,
-            leaderboard_type,
-            score,
-            additional_data,
-            now
-        )
-        .execute(db)
-        .await?;
-
-        Ok(())
-    }
-
-    /// Calculate score for a night completion
-    pub fn calculate_night_score(
-        night: i32,
-        survived: bool,
-        final_power: i32,
-        time_seconds: i32,
-        star_rating: i32,
-        easy_mode: bool,
-    ) -> i64 {
-        if !survived {
-            return 0;
+        let Some(user_id) = session.user_id else {
+            return Ok(());
+        };
+
+        let additional_data = serde_json::json!({
+            "session_id": session.id,
+            "night": session.night_number,
+            "final_power": session.final_power,
+            "time_seconds": session.time_survived_seconds,
+            "star_rating": session.star_rating,
+            "easy_mode": session.easy_mode,
+        });
+
+        let mut submissions: Vec<(String, i64)> = Vec::new();
+
+        match session.session_type.as_str() {
+            "night" if session.survived == Some(true) => {
+                if let Some(night) = session.night_number {
+                    submissions.push((format!("night_{}", night), score));
+                }
+                if let Some(time_seconds) = session.time_survived_seconds {
+                    submissions.push(("speed_run".to_string(), time_seconds as i64));
+                }
+            }
+            "survival" => submissions.push(("survival".to_string(), score)),
+            _ => {}
         }
 
-        let mut score: i64 = 0;
-
-        // Base score per night
-        score += (night as i64) * 1000;
-
-        // Power bonus (max 500)
-        score += (final_power as i64) * 5;
-
-        // Star rating bonus (max 500)
-        score += (star_rating as i64) * 100;
-
-        // Speed bonus (inverse of time, max ~500 for fast completion)
-        let speed_bonus = (600 - time_seconds).max(0) as i64;
-        score += speed_bonus;
+        if session.photos_taken > 0 {
+            submissions.push(("photos".to_string(), session.photos_taken as i64));
+        }
 
-        // Easy mode penalty
-        if easy_mode {
-            score = (score as f64 * 0.5) as i64;
+        for (leaderboard_type, board_score) in submissions {
+            let eligible = find_leaderboard(&leaderboard_type).map_or(false, |board| board.accepts(session));
+            if !eligible {
+                continue;
+            }
+
+            Self::submit_score(
+                tx,
+                user_id,
+                username,
+                &leaderboard_type,
+                board_score,
+                Some(session),
+                Some(additional_data.clone()),
+            )
+            .await?;
         }
 
-        score
+        Ok(())
     }
 
-    /// Calculate score for survival mode
-    pub fn calculate_survival_score(
-        time_survived_seconds: i32,
-        animatronics_avoided: i32,
-        rooms_explored: i32,
-        photos_taken: i32,
-        pizza_slices: i32,
-    ) -> i64 {
-        let mut score: i64 = 0;
-
-        // Time is primary factor
-        score += (time_survived_seconds as i64) * 10;
-
-        // Bonuses for achievements
-        score += (animatronics_avoided as i64) * 50;
-        score += (rooms_explored as i64) * 25;
-        score += (photos_taken as i64) * 30;
-        score += (pizza_slices as i64) * 100;
+    /// Submit a score to the leaderboard
+    /// Updates the user's entry for the current day, week, month and all time if the score is better
+    /// by the board's sort direction, an equal score keeps the earlier entry.
+    /// Boards that don't keep the best score always take the new one.
+    /// Runs in the caller's transaction so the entries commit together with whatever earned them
+    pub async fn submit_score(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+        username: &str,
+        leaderboard_type: &str,
+        score: i64,
+        session: Option<&GameSession>,
+        additional_data: Option<serde_json::Value>,
+    ) -> Result<(), AppError> {
+        let board = find_leaderboard(leaderboard_type).ok_or_else(|| {
+            AppError::Internal(format!("Unknown leaderboard type {}", leaderboard_type))
+        })?;
+        let rank_score = board.rank_score(score);
+
+        // A run counts for the periods it ended in, which matters when sessions are rescored later
+        let achieved_at = session.and_then(|s| s.ended_at).unwrap_or_else(Utc::now);
+        let achieved_on = achieved_at.date_naive();
+        let session_id = session.map(|s| s.id);
+        let time_seconds = session.and_then(|s| s.time_survived_seconds);
+
+        let season = SeasonService::current(&mut **tx).await?;
+        for timeframe in TIMEFRAMES {
+            // Upsert: insert or update if the score is better
+            sqlx::query!(
+                r#"
+                INSERT INTO leaderboard_entries
+                    (id, user_id, username, leaderboard_type, score, rank_score, additional_data,
+                     achieved_at, period, period_start, session_id, time_seconds, season_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                ON CONFLICT (season_id, user_id, leaderboard_type, period, period_start)
+                DO UPDATE SET
+                    score = CASE
+                        WHEN NOT $14 OR EXCLUDED.rank_score > leaderboard_entries.rank_score THEN EXCLUDED.score
+                        ELSE leaderboard_entries.score
+                    END,
+                    additional_data = CASE
+                        WHEN NOT $14 OR EXCLUDED.rank_score > leaderboard_entries.rank_score THEN EXCLUDED.additional_data
+                        ELSE leaderboard_entries.additional_data
+                    END,
+                    achieved_at = CASE
+                        WHEN NOT $14 OR EXCLUDED.rank_score > leaderboard_entries.rank_score THEN EXCLUDED.achieved_at
+                        ELSE leaderboard_entries.achieved_at
+                    END,
+                    session_id = CASE
+                        WHEN NOT $14 OR EXCLUDED.rank_score > leaderboard_entries.rank_score THEN EXCLUDED.session_id
+                        ELSE leaderboard_entries.session_id
+                    END,
+                    time_seconds = CASE
+                        WHEN NOT $14 OR EXCLUDED.rank_score > leaderboard_entries.rank_score THEN EXCLUDED.time_seconds
+                        ELSE leaderboard_entries.time_seconds
+                    END,
+                    rank_score = CASE
+                        WHEN NOT $14 THEN EXCLUDED.rank_score
+                        ELSE GREATEST(leaderboard_entries.rank_score, EXCLUDED.rank_score)
+                    END
+                "#,
+                Uuid::new_v4(),
+                user_id,
+                username,
+                leaderboard_type,
+                score,
+                rank_score,
+                additional_data.clone(),
+                achieved_at,
+                timeframe.as_str(),
+                timeframe.period_start(achieved_on),
+                session_id,
+                time_seconds,
+                season.id,
+                board.keeps_best()
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
 
-        score
+        Ok(())
     }
 }
@@ -0,0 +1,315 @@
+// Matchmaking service - queues players for ranked versus and turns pairs into rooms
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    matchmaking::{self, Pairing, QueuedPlayer},
+    models::*,
+    routes::multiplayer::generate_room_code,
+    services::RatingService,
+    simulation::{NIGHT_HOURS, TICKS_PER_HOUR, TICK_MS},
+};
+
+/// How often the queue is searched for pairs
+const MATCHMAKING_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Matched players have this long to connect and ready up, after that the room is closed unrated
+const MATCH_START_TIMEOUT_MINUTES: i64 = 2;
+
+/// A started match is over when the night is, a room still playing this long after the
+/// night would have ended lost its simulation and is closed unrated
+const MATCH_OVERTIME_MINUTES: i64 = 2;
+
+/// When a ranked room started at or before this point should have finished by now
+fn stale_playing_cutoff(now: DateTime<Utc>) -> DateTime<Utc> {
+    let night = Duration::milliseconds(NIGHT_HOURS as i64 * TICKS_PER_HOUR as i64 * TICK_MS as i64);
+    now - night - Duration::minutes(MATCH_OVERTIME_MINUTES)
+}
+
+pub struct MatchmakingService;
+
+impl MatchmakingService {
+    /// Queue a user for ranked versus. `notify` receives the match when it is made
+    pub async fn join(
+        db: &PgPool,
+        user_id: Uuid,
+        role: &str,
+        notify: Option<mpsc::UnboundedSender<String>>,
+    ) -> Result<QueueStatusResponse, AppError> {
+        if let Some(matched) = Self::pending_match(db, user_id).await? {
+            return Err(AppError::Conflict(format!(
+                "You already have a ranked match in room {}",
+                matched.room_code
+            )));
+        }
+
+        let username = sqlx::query_scalar!("SELECT username FROM users WHERE id = $1", user_id)
+            .fetch_one(db)
+            .await?;
+        let ratings = RatingService::ratings(db, user_id).await?;
+        let rating = |role: &str| {
+            ratings
+                .iter()
+                .find(|r| r.role == role)
+                .map_or(DEFAULT_RATING, |r| r.rating)
+        };
+
+        let now = Utc::now();
+        let player = QueuedPlayer {
+            user_id,
+            username,
+            role: role.to_string(),
+            guard_rating: rating(ROOM_ROLE_GUARD),
+            animatronic_rating: rating(ROOM_ROLE_ANIMATRONIC),
+            queued_at: now,
+            notify,
+        };
+        let status = player.status(now);
+        matchmaking::enqueue(player);
+
+        Ok(status)
+    }
+
+    /// The user's place in the queue, or the ranked room they were matched into
+    pub async fn status(db: &PgPool, user_id: Uuid) -> Result<QueueStatusResponse, AppError> {
+        if let Some(status) = matchmaking::queued_status(user_id, Utc::now()) {
+            return Ok(status);
+        }
+
+        let matched = Self::pending_match(db, user_id).await?;
+        Ok(QueueStatusResponse {
+            status: if matched.is_some() { QUEUE_MATCHED } else { QUEUE_IDLE }.to_string(),
+            role: matched.as_ref().map(|m| m.role.clone()),
+            queued_at: None,
+            search_window: None,
+            matched,
+        })
+    }
+
+    /// The ranked room a user was matched into that has not finished yet
+    async fn pending_match(db: &PgPool, user_id: Uuid) -> Result<Option<MatchFound>, AppError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT
+                r.room_code,
+                m.guard_user_id,
+                m.guard_rating,
+                m.animatronic_rating,
+                g.username AS "guard_username?",
+                a.username AS "animatronic_username?"
+            FROM ranked_matches m
+            JOIN multiplayer_rooms r ON r.id = m.room_id
+            LEFT JOIN users g ON g.id = m.guard_user_id
+            LEFT JOIN users a ON a.id = m.animatronic_user_id
+            WHERE (m.guard_user_id = $1 OR m.animatronic_user_id = $1) AND r.status != $2
+              AND NOT (r.status = $3 AND r.started_at < $4)
+            ORDER BY m.created_at DESC
+            LIMIT 1
+            "#,
+            user_id,
+            ROOM_FINISHED,
+            ROOM_PLAYING,
+            stale_playing_cutoff(Utc::now())
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(row.map(|row| {
+            if row.guard_user_id == Some(user_id) {
+                MatchFound {
+                    room_code: row.room_code,
+                    role: ROOM_ROLE_GUARD.to_string(),
+                    rating: row.guard_rating,
+                    opponent: row.animatronic_username.unwrap_or_default(),
+                    opponent_rating: row.animatronic_rating,
+                }
+            } else {
+                MatchFound {
+                    room_code: row.room_code,
+                    role: ROOM_ROLE_ANIMATRONIC.to_string(),
+                    rating: row.animatronic_rating,
+                    opponent: row.guard_username.unwrap_or_default(),
+                    opponent_rating: row.guard_rating,
+                }
+            }
+        }))
+    }
+
+    /// Create the ranked room for a pair, with both players joined in their roles
+    pub async fn create_match(db: &PgPool, pairing: &Pairing) -> Result<String, AppError> {
+        let now = Utc::now();
+        let room_id = Uuid::new_v4();
+        let room_code = generate_room_code();
+        let guard_rating = pairing.guard.rating(ROOM_ROLE_GUARD);
+        let animatronic_rating = pairing.animatronic.rating(ROOM_ROLE_ANIMATRONIC);
+
+        let mut tx = db.begin().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO multiplayer_rooms
+                (id, room_code, host_user_id, game_mode, max_players, current_players, settings, created_at, ranked)
+            VALUES ($1, $2, NULL, $3, 2, 0, $4, $5, true)
+            "#,
+            room_id,
+            room_code,
+            GAME_MODE_VERSUS,
+            serde_json::json!({ "night": RANKED_NIGHT }),
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for (player, role) in [
+            (&pairing.guard, ROOM_ROLE_GUARD),
+            (&pairing.animatronic, ROOM_ROLE_ANIMATRONIC),
+        ] {
+            sqlx::query!(
+                r#"
+                INSERT INTO multiplayer_participants (id, room_id, user_id, role, joined_at)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                Uuid::new_v4(),
+                room_id,
+                player.user_id,
+                role,
+                now
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query!(
+                "INSERT INTO player_ratings (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                player.user_id,
+                role
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            r#"
+            INSERT INTO ranked_matches (room_id, guard_user_id, animatronic_user_id, guard_rating, animatronic_rating)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            room_id,
+            pairing.guard.user_id,
+            pairing.animatronic.user_id,
+            guard_rating,
+            animatronic_rating
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        for (player, role, opponent) in [
+            (&pairing.guard, ROOM_ROLE_GUARD, &pairing.animatronic),
+            (&pairing.animatronic, ROOM_ROLE_ANIMATRONIC, &pairing.guard),
+        ] {
+            if let Some(notify) = &player.notify {
+                let opponent_role = if role == ROOM_ROLE_GUARD {
+                    ROOM_ROLE_ANIMATRONIC
+                } else {
+                    ROOM_ROLE_GUARD
+                };
+                let msg = MatchmakingServerMessage::MatchFound {
+                    matched: MatchFound {
+                        room_code: room_code.clone(),
+                        role: role.to_string(),
+                        rating: player.rating(role),
+                        opponent: opponent.username.clone(),
+                        opponent_rating: opponent.rating(opponent_role),
+                    },
+                };
+                let _ = notify.send(serde_json::to_string(&msg).unwrap());
+            }
+        }
+
+        Ok(room_code)
+    }
+
+    /// Close ranked rooms whose players never showed up, the match stays unrated
+    pub async fn close_unstarted(db: &PgPool) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let cutoff = now - Duration::minutes(MATCH_START_TIMEOUT_MINUTES);
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE multiplayer_rooms SET status = $1, ended_at = $2
+            WHERE ranked AND status = $3 AND created_at < $4 AND current_players < 2
+            "#,
+            ROOM_FINISHED,
+            now,
+            ROOM_WAITING,
+            cutoff
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Close ranked rooms still playing long after their night, e.g. after everyone left or
+    /// the server restarted mid-match. The match stays unrated
+    pub async fn close_stale(db: &PgPool) -> Result<u64, AppError> {
+        let now = Utc::now();
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE multiplayer_rooms SET status = $1, ended_at = $2
+            WHERE ranked AND status = $3 AND started_at < $4
+            "#,
+            ROOM_FINISHED,
+            now,
+            ROOM_PLAYING,
+            stale_playing_cutoff(now)
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Pair queued players for as long as the server runs
+    pub fn spawn_matchmaking_task(db: PgPool) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MATCHMAKING_INTERVAL);
+            loop {
+                interval.tick().await;
+
+                for pairing in matchmaking::take_pairings(Utc::now()) {
+                    match Self::create_match(&db, &pairing).await {
+                        Ok(room_code) => tracing::info!(
+                            "Matched {} and {} into ranked room {}",
+                            pairing.guard.username,
+                            pairing.animatronic.username,
+                            room_code
+                        ),
+                        Err(e) => {
+                            tracing::error!("Failed to create ranked match: {:?}", e);
+                            // Back in line, they keep their place
+                            matchmaking::enqueue(pairing.guard);
+                            matchmaking::enqueue(pairing.animatronic);
+                        }
+                    }
+                }
+
+                match Self::close_unstarted(&db).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("Closed {} unstarted ranked rooms", count),
+                    Err(e) => tracing::error!("Failed to close unstarted ranked rooms: {:?}", e),
+                }
+
+                match Self::close_stale(&db).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("Closed {} stale ranked rooms", count),
+                    Err(e) => tracing::error!("Failed to close stale ranked rooms: {:?}", e),
+                }
+            }
+        });
+    }
+}
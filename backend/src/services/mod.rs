@@ -1,7 +1,27 @@
+pub mod achievement_service;
+pub mod anti_cheat_service;
 pub mod auth_service;
 pub mod challenge_service;
+pub mod friend_service;
 pub mod leaderboard_service;
+pub mod mailer;
+pub mod matchmaking_service;
+pub mod rating_service;
+pub mod replay_service;
+pub mod scoring_service;
+pub mod season_service;
+pub mod session_service;
 
+pub use achievement_service::*;
+pub use anti_cheat_service::*;
 pub use auth_service::*;
 pub use challenge_service::*;
+pub use friend_service::*;
 pub use leaderboard_service::*;
+pub use mailer::*;
+pub use matchmaking_service::*;
+pub use rating_service::*;
+pub use replay_service::*;
+pub use scoring_service::*;
+pub use season_service::*;
+pub use session_service::*;
@@ -0,0 +1,203 @@
+// Rating service - Elo ratings per role for ranked versus
+use chrono::Utc;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{error::AppError, models::*, services::LeaderboardService};
+
+/// Ratings move faster while a player has few games in a role
+const PROVISIONAL_GAMES: i32 = 10;
+const PROVISIONAL_K_FACTOR: f64 = 40.0;
+const K_FACTOR: f64 = 24.0;
+
+pub struct RatingService;
+
+impl RatingService {
+    /// A user's rating in every role, roles they have not played yet are at `DEFAULT_RATING`
+    pub async fn ratings(db: impl PgExecutor<'_>, user_id: Uuid) -> Result<Vec<PlayerRating>, AppError> {
+        let rows = sqlx::query_as!(
+            PlayerRating,
+            "SELECT * FROM player_ratings WHERE user_id = $1",
+            user_id
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(ROOM_ROLES
+            .iter()
+            .map(|role| {
+                rows.iter()
+                    .find(|rating| rating.role == *role)
+                    .cloned()
+                    .unwrap_or_else(|| PlayerRating {
+                        user_id,
+                        role: role.to_string(),
+                        rating: DEFAULT_RATING,
+                        games_played: 0,
+                        wins: 0,
+                        losses: 0,
+                        updated_at: Utc::now(),
+                    })
+            })
+            .collect())
+    }
+
+    /// Chance of beating the opponent, between 0 and 1
+    pub fn expected_score(rating: i32, opponent_rating: i32) -> f64 {
+        1.0 / (1.0 + 10f64.powf((opponent_rating - rating) as f64 / 400.0))
+    }
+
+    pub fn rating_change(rating: i32, opponent_rating: i32, games_played: i32, won: bool) -> i32 {
+        let k = if games_played < PROVISIONAL_GAMES {
+            PROVISIONAL_K_FACTOR
+        } else {
+            K_FACTOR
+        };
+        let actual = if won { 1.0 } else { 0.0 };
+
+        (k * (actual - Self::expected_score(rating, opponent_rating))).round() as i32
+    }
+
+    /// Record who won a ranked room and update both players' ratings and the ranked boards.
+    /// Returns None if the room is not ranked or its result was already recorded
+    pub async fn record_result(
+        db: &PgPool,
+        room_id: Uuid,
+        winner_role: &str,
+    ) -> Result<Option<RankedMatch>, AppError> {
+        let now = Utc::now();
+        let mut tx = db.begin().await?;
+
+        let Some(ranked_match) = sqlx::query_as!(
+            RankedMatch,
+            "SELECT * FROM ranked_matches WHERE room_id = $1 AND finished_at IS NULL FOR UPDATE",
+            room_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let guard = Self::lock_rating(&mut tx, ranked_match.guard_user_id, ROOM_ROLE_GUARD).await?;
+        let animatronic =
+            Self::lock_rating(&mut tx, ranked_match.animatronic_user_id, ROOM_ROLE_ANIMATRONIC).await?;
+
+        // Players whose account is gone are rated as they were when matched
+        let guard_rating = guard.as_ref().map_or(ranked_match.guard_rating, |r| r.rating);
+        let animatronic_rating = animatronic
+            .as_ref()
+            .map_or(ranked_match.animatronic_rating, |r| r.rating);
+        let guard_won = winner_role == ROOM_ROLE_GUARD;
+
+        let guard_change = Self::rating_change(
+            guard_rating,
+            animatronic_rating,
+            guard.as_ref().map_or(0, |r| r.games_played),
+            guard_won,
+        );
+        let animatronic_change = Self::rating_change(
+            animatronic_rating,
+            guard_rating,
+            animatronic.as_ref().map_or(0, |r| r.games_played),
+            !guard_won,
+        );
+
+        let mut updated = Vec::new();
+        for (rating, change, won) in [
+            (guard, guard_change, guard_won),
+            (animatronic, animatronic_change, !guard_won),
+        ] {
+            let Some(rating) = rating else {
+                continue;
+            };
+
+            let rating = sqlx::query_as!(
+                PlayerRating,
+                r#"
+                UPDATE player_ratings
+                SET rating = rating + $1,
+                    games_played = games_played + 1,
+                    wins = wins + $2,
+                    losses = losses + $3,
+                    updated_at = $4
+                WHERE user_id = $5 AND role = $6
+                RETURNING *
+                "#,
+                change,
+                won as i32,
+                !won as i32,
+                now,
+                rating.user_id,
+                rating.role
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            updated.push(rating);
+        }
+
+        let ranked_match = sqlx::query_as!(
+            RankedMatch,
+            r#"
+            UPDATE ranked_matches
+            SET winner_role = $1, guard_rating_change = $2, animatronic_rating_change = $3, finished_at = $4
+            WHERE id = $5
+            RETURNING *
+            "#,
+            winner_role,
+            guard_change,
+            animatronic_change,
+            now,
+            ranked_match.id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        // The ranked boards show the rating each player has now
+        for rating in updated {
+            let username = sqlx::query_scalar!("SELECT username FROM users WHERE id = $1", rating.user_id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+            LeaderboardService::submit_score(
+                &mut tx,
+                rating.user_id,
+                &username,
+                &ranked_board_id(&rating.role),
+                rating.rating as i64,
+                None,
+                Some(serde_json::json!({
+                    "games_played": rating.games_played,
+                    "wins": rating.wins,
+                    "losses": rating.losses,
+                })),
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(Some(ranked_match))
+    }
+
+    async fn lock_rating(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Option<Uuid>,
+        role: &str,
+    ) -> Result<Option<PlayerRating>, AppError> {
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        let rating = sqlx::query_as!(
+            PlayerRating,
+            "SELECT * FROM player_ratings WHERE user_id = $1 AND role = $2 FOR UPDATE",
+            user_id,
+            role
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(rating)
+    }
+}
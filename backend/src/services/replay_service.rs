@@ -0,0 +1,164 @@
+// Replay service - re-runs night sessions from their input log to verify the result
+use sqlx::PgExecutor;
+use uuid::Uuid;
+
+use crate::{
+    error::AppError,
+    models::*,
+    simulation::{
+        AiLevels, Intent, NightConfig, NightOutcome, NightSimulation, NIGHT_HOURS, TICKS_PER_HOUR,
+        TICK_MS,
+    },
+};
+
+const MAX_REPLAY_INPUTS: usize = 20_000;
+
+/// No input can come after the night is over
+const NIGHT_MS: u64 = NIGHT_HOURS as u64 * TICKS_PER_HOUR as u64 * TICK_MS;
+
+/// Reported power and time may be off by one because the client rounds differently
+const REPORT_TOLERANCE: i32 = 1;
+
+pub struct ReplayService;
+
+impl ReplayService {
+    /// Re-run the night from `req.replay` and replace the reported fields with the simulated
+    /// ones. Returns why the reported result can't be trusted, empty when the replay matches.
+    /// Night sessions always need a replay, other session types can't have one
+    pub fn verify(
+        session: &GameSession,
+        req: &mut UpdateSessionRequest,
+    ) -> Result<Vec<String>, AppError> {
+        if session.session_type != "night" {
+            if req.replay.is_some() {
+                return Err(AppError::BadRequest(
+                    "Replays are only supported for night sessions".to_string(),
+                ));
+            }
+            return Ok(Vec::new());
+        }
+
+        let Some(replay) = &req.replay else {
+            return Ok(vec!["night session has no replay".to_string()]);
+        };
+
+        let Some(seed) = session.seed else {
+            return Ok(vec![
+                "session was started without a seed and can't be replayed".to_string(),
+            ]);
+        };
+
+        if replay.inputs.len() > MAX_REPLAY_INPUTS {
+            return Err(AppError::BadRequest("Replay has too many inputs".to_string()));
+        }
+
+        let mut inputs = Vec::with_capacity(replay.inputs.len());
+        let mut last_ms = 0;
+        for input in &replay.inputs {
+            if input.at_ms < last_ms {
+                return Err(AppError::BadRequest(
+                    "Replay inputs must be in chronological order".to_string(),
+                ));
+            }
+            if input.at_ms > NIGHT_MS {
+                return Err(AppError::BadRequest(
+                    "Replay inputs must fall within the night".to_string(),
+                ));
+            }
+            last_ms = input.at_ms;
+
+            let action = GameAction {
+                action_type: input.action_type.clone(),
+                data: input.data.clone(),
+            };
+            let intent = Intent::from_action(&action).map_err(AppError::BadRequest)?;
+            if intent.role() != "guard" {
+                return Err(AppError::BadRequest(format!(
+                    "'{}' is not a guard action",
+                    input.action_type
+                )));
+            }
+
+            inputs.push(((input.at_ms / TICK_MS) as u32, intent));
+        }
+
+        let night = session.night_number.unwrap_or(1);
+        let custom_levels = session
+            .custom_difficulty
+            .clone()
+            .and_then(|levels| serde_json::from_value::<AiLevels>(levels).ok())
+            .map(AiLevels::clamped);
+        let mut config = NightConfig::new(night, custom_levels, session.easy_mode);
+        config.starting_power = session.starting_power;
+
+        let mut sim = NightSimulation::new(config, seed as u64);
+        sim.run(&inputs).map_err(AppError::BadRequest)?;
+
+        let state = sim.state();
+        let survived = sim.outcome() == Some(NightOutcome::Survived);
+        let final_power = sim.power_percent();
+        let time_survived_seconds = sim.elapsed_seconds();
+
+        let mut mismatches = Vec::new();
+        if req.survived.map_or(false, |s| s != survived) {
+            mismatches.push("survived");
+        }
+        if req.final_power.map_or(false, |p| (p - final_power).abs() > REPORT_TOLERANCE) {
+            mismatches.push("final_power");
+        }
+        if req
+            .time_survived_seconds
+            .map_or(false, |t| (t - time_survived_seconds).abs() > REPORT_TOLERANCE)
+        {
+            mismatches.push("time_survived_seconds");
+        }
+
+        if !mismatches.is_empty() {
+            return Ok(vec![format!(
+                "replay does not match the reported {}",
+                mismatches.join(", ")
+            )]);
+        }
+
+        req.survived = Some(survived);
+        req.final_power = Some(final_power);
+        req.time_survived_seconds = Some(time_survived_seconds);
+        req.death_by = match sim.outcome() {
+            Some(NightOutcome::Jumpscare { by }) => Some(by.as_str().to_string()),
+            _ => None,
+        };
+        req.cameras_used = Some(state.cameras_used);
+        req.power_out = Some(state.power_out);
+        req.left_door_used = Some(state.left_door_used);
+        req.right_door_used = Some(state.right_door_used);
+
+        Ok(Vec::new())
+    }
+
+    /// Store the input log of a verified session, replacing any earlier one
+    pub async fn save(
+        db: impl PgExecutor<'_>,
+        session_id: Uuid,
+        seed: i64,
+        replay: &ReplayLog,
+    ) -> Result<(), AppError> {
+        let inputs = serde_json::to_value(&replay.inputs)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO session_replays (session_id, seed, inputs)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (session_id)
+            DO UPDATE SET seed = EXCLUDED.seed, inputs = EXCLUDED.inputs, created_at = NOW()
+            "#,
+            session_id,
+            seed,
+            inputs
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
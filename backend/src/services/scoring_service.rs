@@ -0,0 +1,168 @@
+// Scoring service - recomputes stored scores and leaderboards after the rules change
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use std::collections::HashMap;
+
+use crate::{
+    error::AppError,
+    models::*,
+    scoring,
+    services::{LeaderboardService, SeasonService},
+};
+
+#[derive(Debug, Default)]
+pub struct RescoreSummary {
+    pub sessions_rescored: u64,
+    pub sessions_resubmitted: u64,
+}
+
+pub struct ScoringService;
+
+impl ScoringService {
+    /// Rescore finished sessions scored under an older rule version, then rebuild the
+    /// running season's session boards from its sessions. Archived seasons are left as they ended.
+    /// Runs in one transaction so the boards are never seen half rebuilt
+    pub async fn rescore(db: &PgPool) -> Result<RescoreSummary, AppError> {
+        let rules = scoring::current_rules();
+        let mut summary = RescoreSummary::default();
+        let mut tx = db.begin().await?;
+
+        let stale = sqlx::query_as!(
+            GameSession,
+            r#"
+            SELECT * FROM game_sessions
+            WHERE status = $1 AND survived IS NOT NULL
+              AND scoring_version IS DISTINCT FROM $2
+            "#,
+            SESSION_FINISHED,
+            rules.version
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for session in &stale {
+            let scored = rules.score_session(session);
+            sqlx::query!(
+                r#"
+                UPDATE game_sessions
+                SET score = $1, star_rating = COALESCE($2, star_rating), scoring_version = $3
+                WHERE id = $4
+                "#,
+                scored.score as i32,
+                scored.star_rating,
+                rules.version,
+                session.id
+            )
+            .execute(&mut *tx)
+            .await?;
+            summary.sessions_rescored += 1;
+        }
+
+        let season = SeasonService::current(&mut *tx).await?;
+        let session_boards = session_boards();
+
+        sqlx::query!(
+            "DELETE FROM leaderboard_entries WHERE season_id = $1 AND leaderboard_type = ANY($2)",
+            season.id,
+            &session_boards as &[&str]
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let sessions = sqlx::query_as!(
+            GameSession,
+            r#"
+            SELECT * FROM game_sessions
+            WHERE status = $1 AND survived IS NOT NULL AND user_id IS NOT NULL AND ended_at >= $2
+            ORDER BY ended_at
+            "#,
+            SESSION_FINISHED,
+            season.starts_at
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut usernames: HashMap<_, String> = HashMap::new();
+        for session in &sessions {
+            let Some(user_id) = session.user_id else {
+                continue;
+            };
+            let username = match usernames.get(&user_id) {
+                Some(username) => username.clone(),
+                None => {
+                    let username = sqlx::query_scalar!("SELECT username FROM users WHERE id = $1", user_id)
+                        .fetch_one(&mut *tx)
+                        .await?;
+                    usernames.insert(user_id, username.clone());
+                    username
+                }
+            };
+
+            LeaderboardService::submit_session(&mut tx, session, &username, session.score as i64).await?;
+            summary.sessions_resubmitted += 1;
+        }
+
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+
+    /// Rebuild one user's session boards in the running season from their finished sessions,
+    /// after one of them stopped counting. Returns how many sessions were resubmitted
+    pub async fn rebuild_user(
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: Uuid,
+    ) -> Result<u64, AppError> {
+        let season = SeasonService::current(&mut **tx).await?;
+        let session_boards = session_boards();
+
+        sqlx::query!(
+            r#"
+            DELETE FROM leaderboard_entries
+            WHERE season_id = $1 AND user_id = $2 AND leaderboard_type = ANY($3)
+            "#,
+            season.id,
+            user_id,
+            &session_boards as &[&str]
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let sessions = sqlx::query_as!(
+            GameSession,
+            r#"
+            SELECT * FROM game_sessions
+            WHERE status = $1 AND survived IS NOT NULL AND user_id = $2 AND ended_at >= $3
+            ORDER BY ended_at
+            "#,
+            SESSION_FINISHED,
+            user_id,
+            season.starts_at
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        if sessions.is_empty() {
+            return Ok(0);
+        }
+
+        let username = sqlx::query_scalar!("SELECT username FROM users WHERE id = $1", user_id)
+            .fetch_one(&mut **tx)
+            .await?;
+
+        for session in &sessions {
+            LeaderboardService::submit_session(tx, session, &username, session.score as i64).await?;
+        }
+
+        Ok(sessions.len() as u64)
+    }
+}
+
+/// Boards fed by session results, the ones a rescore rebuilds
+fn session_boards() -> Vec<&'static str> {
+    LEADERBOARDS
+        .iter()
+        .filter(|board| matches!(board.source, ScoreSource::Session(_) | ScoreSource::AnySession))
+        .map(|board| board.id)
+        .collect()
+}
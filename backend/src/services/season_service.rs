@@ -0,0 +1,152 @@
+// Season service - the running season, and rollover into the next one when it ends
+use chrono::{Duration, Utc};
+use sqlx::{PgExecutor, PgPool};
+
+use crate::{error::AppError, models::*};
+
+/// How often the running season is checked for its end
+const ROLLOVER_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub struct SeasonService;
+
+impl SeasonService {
+    pub async fn current(db: impl PgExecutor<'_>) -> Result<Season, AppError> {
+        sqlx::query_as!(
+            Season,
+            "SELECT * FROM seasons WHERE status = $1",
+            SEASON_ACTIVE
+        )
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::Internal("No active season".to_string()))
+    }
+
+    /// If the running season has ended, freeze its final standings, grant its rewards,
+    /// archive it and start the next season. Returns the new season
+    pub async fn rollover(db: &PgPool) -> Result<Option<Season>, AppError> {
+        let now = Utc::now();
+        let mut tx = db.begin().await?;
+
+        let Some(season) = sqlx::query_as!(
+            Season,
+            "SELECT * FROM seasons WHERE status = $1 AND ends_at <= $2 FOR UPDATE",
+            SEASON_ACTIVE,
+            now
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        // Final standings are the all-time boards of the season, ranked like the live boards
+        sqlx::query!(
+            r#"
+            INSERT INTO season_standings
+                (season_id, leaderboard_type, user_id, username, final_rank, score, achieved_at, session_id)
+            SELECT
+                season_id,
+                leaderboard_type,
+                user_id,
+                username,
+                RANK() OVER (
+                    PARTITION BY leaderboard_type
+                    ORDER BY rank_score DESC, achieved_at ASC, time_seconds ASC NULLS LAST
+                ),
+                score,
+                achieved_at,
+                session_id
+            FROM leaderboard_entries
+            WHERE season_id = $1 AND period = $2
+            ON CONFLICT (season_id, leaderboard_type, user_id) DO NOTHING
+            "#,
+            season.id,
+            Timeframe::All.as_str()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if let Some(skin) = &season.reward_skin {
+            sqlx::query!(
+                "UPDATE season_standings SET reward_skin = $1 WHERE season_id = $2 AND final_rank <= $3",
+                skin,
+                season.id,
+                SEASON_REWARD_TOP
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            let rewarded = sqlx::query!(
+                r#"
+                UPDATE player_profiles
+                SET unlocked_skins = COALESCE(unlocked_skins, '[]'::jsonb) || jsonb_build_array($1::text),
+                    updated_at = $2
+                WHERE user_id IN (
+                        SELECT user_id FROM season_standings WHERE season_id = $3 AND final_rank <= $4
+                    )
+                  AND NOT COALESCE(unlocked_skins, '[]'::jsonb) ? $1
+                "#,
+                skin,
+                now,
+                season.id,
+                SEASON_REWARD_TOP
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            tracing::info!("Granted {} to {} players for {}", skin, rewarded, season.name);
+        }
+
+        sqlx::query!(
+            "UPDATE seasons SET status = $1, archived_at = $2 WHERE id = $3",
+            SEASON_ARCHIVED,
+            now,
+            season.id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // After downtime the next season still gets its full length
+        let length = Duration::days(SEASON_LENGTH_DAYS);
+        let starts_at = season.ends_at;
+        let ends_at = (starts_at + length).max(now + length);
+        let number = season.number + 1;
+
+        let next = sqlx::query_as!(
+            Season,
+            r#"
+            INSERT INTO seasons (number, name, starts_at, ends_at, reward_skin, status)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING *
+            "#,
+            number,
+            format!("Season {}", number),
+            starts_at,
+            ends_at,
+            format!("season_{}_medal", number),
+            SEASON_ACTIVE
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(next))
+    }
+
+    /// Periodically roll seasons over for as long as the server runs
+    pub fn spawn_rollover_task(db: PgPool) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ROLLOVER_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                match Self::rollover(&db).await {
+                    Ok(None) => {}
+                    Ok(Some(season)) => tracing::info!("Started {}", season.name),
+                    Err(e) => tracing::error!("Failed to roll over the season: {:?}", e),
+                }
+            }
+        });
+    }
+}
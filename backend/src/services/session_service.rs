@@ -0,0 +1,51 @@
+// Session service - background upkeep for game sessions
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::{error::AppError, models::*};
+
+/// How often open sessions are checked for the timeout
+const ABANDON_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+pub struct SessionService;
+
+impl SessionService {
+    /// Mark open sessions without recent activity as abandoned
+    pub async fn abandon_stale(db: &PgPool) -> Result<u64, AppError> {
+        let now = Utc::now();
+        let cutoff = now - Duration::minutes(SESSION_TIMEOUT_MINUTES);
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE game_sessions
+            SET status = $1, ended_at = $2
+            WHERE status IN ($3, $4)
+              AND COALESCE(last_heartbeat_at, started_at) < $5
+            "#,
+            SESSION_ABANDONED,
+            now,
+            SESSION_STARTED,
+            SESSION_IN_PROGRESS,
+            cutoff
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Periodically abandon stale sessions for as long as the server runs
+    pub fn spawn_abandon_task(db: PgPool) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ABANDON_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                match Self::abandon_stale(&db).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("Abandoned {} stale sessions", count),
+                    Err(e) => tracing::error!("Failed to abandon stale sessions: {:?}", e),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,644 @@
+// Night mode rules, ported from game.js
+// Time advances in fixed ticks and all randomness comes from a seeded rng,
+// so the same seed and inputs always produce the same night
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::models::GameAction;
+
+pub const TICK_MS: u64 = 100;
+pub const TICKS_PER_SECOND: u32 = 10;
+pub const TICKS_PER_HOUR: u32 = 90 * TICKS_PER_SECOND;
+pub const NIGHT_HOURS: i32 = 6;
+
+/// Power is tracked in thousandths of a percent to keep the drain exact
+pub const POWER_SCALE: i64 = 1000;
+const POWER_DRAIN_PER_USAGE: i64 = 18; // 0.018% per usage bar per tick
+
+const BONNIE_MOVE_TICKS: u32 = 50;
+const CHICA_MOVE_TICKS: u32 = 50;
+const FREDDY_MOVE_TICKS: u32 = 40;
+const FOXY_MOVE_TICKS: u32 = 60;
+const FOXY_RUN_TICKS: u32 = 15;
+const ATTACK_CHECK_TICKS: u32 = 10;
+const POWER_OUT_ATTACK_TICKS: std::ops::RangeInclusive<u32> = 50..=150;
+
+// Versus mode cooldowns for the animatronic player
+const FORCE_MOVE_COOLDOWN_TICKS: u32 = 5 * TICKS_PER_SECOND;
+const FOXY_SPRINT_COOLDOWN_TICKS: u32 = 15 * TICKS_PER_SECOND;
+
+pub const CAMERAS: &[&str] = &["1A", "1B", "2A", "2B", "3", "4A", "4B"];
+pub const LEFT_DOOR: &str = "LEFT_DOOR";
+pub const RIGHT_DOOR: &str = "RIGHT_DOOR";
+const PIRATE_COVE: &str = "3";
+
+const FREDDY_PATH: &[&str] = &["1A", "1B", "2A", "4A", "4B", RIGHT_DOOR];
+const BONNIE_PATH: &[&str] = &["1A", "1B", "3", "2A", "2B", LEFT_DOOR];
+const CHICA_PATH: &[&str] = &["1A", "1B", "4A", "4B", RIGHT_DOOR];
+
+const EASY_MODE_AI_MULTIPLIER: f64 = 0.6;
+
+/// The most power that can be left after `seconds`, draining only the base usage bar
+pub fn max_power_after(starting_power: i32, seconds: i32) -> i32 {
+    let drained = seconds.max(0) as i64 * TICKS_PER_SECOND as i64 * POWER_DRAIN_PER_USAGE;
+    let remaining = (starting_power as i64 * POWER_SCALE - drained).max(0);
+    ((remaining + POWER_SCALE - 1) / POWER_SCALE) as i32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Animatronic {
+    Freddy,
+    Bonnie,
+    Chica,
+    Foxy,
+}
+
+impl Animatronic {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Animatronic::Freddy => "freddy",
+            Animatronic::Bonnie => "bonnie",
+            Animatronic::Chica => "chica",
+            Animatronic::Foxy => "foxy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LookDirection {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AiLevels {
+    pub freddy: i32,
+    pub bonnie: i32,
+    pub chica: i32,
+    pub foxy: i32,
+}
+
+impl AiLevels {
+    /// AI levels per night, matching `setAILevels`
+    pub fn for_night(night: i32) -> Self {
+        let (freddy, bonnie, chica, foxy) = match night {
+            1 => (0, 3, 2, 1),
+            2 => (1, 5, 4, 2),
+            3 => (2, 7, 6, 4),
+            4 => (4, 9, 8, 6),
+            5 => (6, 12, 11, 8),
+            _ => (10, 15, 15, 10),
+        };
+        AiLevels { freddy, bonnie, chica, foxy }
+    }
+
+    /// Custom night levels are limited to 0-20, like the custom night menu
+    pub fn clamped(self) -> Self {
+        AiLevels {
+            freddy: self.freddy.clamp(0, 20),
+            bonnie: self.bonnie.clamp(0, 20),
+            chica: self.chica.clamp(0, 20),
+            foxy: self.foxy.clamp(0, 20),
+        }
+    }
+
+    fn scaled(self, multiplier: f64) -> Self {
+        let scale = |level: i32| (level as f64 * multiplier).floor() as i32;
+        AiLevels {
+            freddy: scale(self.freddy),
+            bonnie: scale(self.bonnie),
+            chica: scale(self.chica),
+            foxy: scale(self.foxy),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NightConfig {
+    pub night: i32,
+    pub ai_levels: AiLevels,
+    pub starting_power: i32,
+}
+
+impl NightConfig {
+    pub fn new(night: i32, custom_levels: Option<AiLevels>, easy_mode: bool) -> Self {
+        let mut ai_levels = custom_levels.unwrap_or_else(|| AiLevels::for_night(night));
+        if easy_mode {
+            ai_levels = ai_levels.scaled(EASY_MODE_AI_MULTIPLIER);
+        }
+
+        NightConfig {
+            night,
+            ai_levels,
+            starting_power: 100,
+        }
+    }
+}
+
+/// Something a player wants to do, validated and applied by the simulation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action_type", content = "data", rename_all = "snake_case")]
+pub enum Intent {
+    ToggleLeftDoor,
+    ToggleRightDoor,
+    ToggleLeftLight,
+    ToggleRightLight,
+    ToggleCamera,
+    SwitchCamera { camera: String },
+    Look { direction: LookDirection },
+    MoveAnimatronic { animatronic: Animatronic },
+}
+
+impl Intent {
+    pub fn from_action(action: &GameAction) -> Result<Self, String> {
+        match action.action_type.as_str() {
+            "toggle_left_door" => Ok(Intent::ToggleLeftDoor),
+            "toggle_right_door" => Ok(Intent::ToggleRightDoor),
+            "toggle_left_light" => Ok(Intent::ToggleLeftLight),
+            "toggle_right_light" => Ok(Intent::ToggleRightLight),
+            "toggle_camera" => Ok(Intent::ToggleCamera),
+            "switch_camera" => {
+                let camera: String = data_field(action, "camera")?;
+                if !CAMERAS.contains(&camera.as_str()) {
+                    return Err(format!("Unknown camera '{}'", camera));
+                }
+                Ok(Intent::SwitchCamera { camera })
+            }
+            "look" => Ok(Intent::Look {
+                direction: data_field(action, "direction")?,
+            }),
+            "move_animatronic" => Ok(Intent::MoveAnimatronic {
+                animatronic: data_field(action, "animatronic")?,
+            }),
+            other => Err(format!("Unknown action '{}'", other)),
+        }
+    }
+
+    /// The multiplayer role allowed to send this intent
+    pub fn role(&self) -> &'static str {
+        match self {
+            Intent::MoveAnimatronic { .. } => "animatronic",
+            _ => "guard",
+        }
+    }
+}
+
+fn data_field<T: serde::de::DeserializeOwned>(action: &GameAction, name: &str) -> Result<T, String> {
+    let value = action
+        .data
+        .get(name)
+        .cloned()
+        .ok_or_else(|| format!("'{}' requires data.{}", action.action_type, name))?;
+
+    serde_json::from_value(value).map_err(|e| format!("Invalid data.{}: {}", name, e))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum NightOutcome {
+    Survived,
+    Jumpscare { by: Animatronic },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnimatronicPositions {
+    pub freddy: &'static str,
+    pub bonnie: &'static str,
+    pub chica: &'static str,
+    pub foxy: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NightState {
+    pub tick: u32,
+    pub hour: i32,
+    pub power: i64, // thousandths of a percent, see POWER_SCALE
+    pub usage: i32,
+    pub left_door_closed: bool,
+    pub right_door_closed: bool,
+    pub left_light_on: bool,
+    pub right_light_on: bool,
+    pub camera_open: bool,
+    pub current_camera: &'static str,
+    pub office_view: LookDirection,
+    pub positions: AnimatronicPositions,
+    pub foxy_stage: i32,
+    pub power_out: bool,
+    pub move_cooldown_ticks: u32,
+    pub cameras_used: bool,
+    pub left_door_used: bool,
+    pub right_door_used: bool,
+    pub outcome: Option<NightOutcome>,
+}
+
+pub struct NightSimulation {
+    config: NightConfig,
+    seed: u64,
+    rng: StdRng,
+    state: NightState,
+    foxy_run_at: Option<u32>,
+    power_out_attack_at: Option<u32>,
+}
+
+impl NightSimulation {
+    pub fn new(config: NightConfig, seed: u64) -> Self {
+        NightSimulation {
+            config,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            state: NightState {
+                tick: 0,
+                hour: 0,
+                power: config.starting_power as i64 * POWER_SCALE,
+                usage: 1,
+                left_door_closed: false,
+                right_door_closed: false,
+                left_light_on: false,
+                right_light_on: false,
+                camera_open: false,
+                current_camera: "1A",
+                office_view: LookDirection::Center,
+                positions: AnimatronicPositions {
+                    freddy: FREDDY_PATH[0],
+                    bonnie: BONNIE_PATH[0],
+                    chica: CHICA_PATH[0],
+                    foxy: PIRATE_COVE,
+                },
+                foxy_stage: 0,
+                power_out: false,
+                move_cooldown_ticks: 0,
+                cameras_used: false,
+                left_door_used: false,
+                right_door_used: false,
+                outcome: None,
+            },
+            foxy_run_at: None,
+            power_out_attack_at: None,
+        }
+    }
+
+    pub fn config(&self) -> &NightConfig {
+        &self.config
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn state(&self) -> &NightState {
+        &self.state
+    }
+
+    pub fn outcome(&self) -> Option<NightOutcome> {
+        self.state.outcome
+    }
+
+    /// Power as the whole percentage shown to the player
+    pub fn power_percent(&self) -> i32 {
+        ((self.state.power + POWER_SCALE - 1) / POWER_SCALE) as i32
+    }
+
+    pub fn elapsed_seconds(&self) -> i32 {
+        (self.state.tick / TICKS_PER_SECOND) as i32
+    }
+
+    /// Snapshot sent to clients
+    pub fn snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "night": self.config.night,
+            "power_percent": self.power_percent(),
+            "elapsed_seconds": self.elapsed_seconds(),
+            "state": self.state,
+        })
+    }
+
+    /// Apply a player intent between ticks
+    pub fn apply(&mut self, intent: &Intent) -> Result<(), String> {
+        if self.state.outcome.is_some() {
+            return Err("The night is over".to_string());
+        }
+
+        let powered = self.state.power > 0;
+        let state = &mut self.state;
+
+        match intent {
+            // Doors and lights stop working once the power is out
+            Intent::ToggleLeftDoor if powered => {
+                state.left_door_closed = !state.left_door_closed;
+                state.left_door_used = true;
+            }
+            Intent::ToggleRightDoor if powered => {
+                state.right_door_closed = !state.right_door_closed;
+                state.right_door_used = true;
+            }
+            Intent::ToggleLeftLight if powered => state.left_light_on = !state.left_light_on,
+            Intent::ToggleRightLight if powered => state.right_light_on = !state.right_light_on,
+            Intent::ToggleLeftDoor
+            | Intent::ToggleRightDoor
+            | Intent::ToggleLeftLight
+            | Intent::ToggleRightLight => {}
+            Intent::ToggleCamera => {
+                state.camera_open = !state.camera_open;
+                state.cameras_used |= state.camera_open;
+            }
+            Intent::SwitchCamera { camera } => {
+                state.current_camera = CAMERAS
+                    .iter()
+                    .find(|c| **c == camera.as_str())
+                    .ok_or_else(|| format!("Unknown camera '{}'", camera))?;
+            }
+            Intent::Look { direction } => {
+                if !state.camera_open {
+                    state.office_view = *direction;
+                }
+            }
+            Intent::MoveAnimatronic { animatronic } => {
+                if state.move_cooldown_ticks > 0 {
+                    return Err("Move is on cooldown".to_string());
+                }
+
+                if *animatronic == Animatronic::Foxy {
+                    self.state.foxy_stage = 4;
+                    self.foxy_run();
+                    self.state.move_cooldown_ticks = FOXY_SPRINT_COOLDOWN_TICKS;
+                } else {
+                    self.advance(*animatronic);
+                    self.state.move_cooldown_ticks = FORCE_MOVE_COOLDOWN_TICKS;
+                }
+            }
+        }
+
+        self.update_usage();
+        Ok(())
+    }
+
+    /// Advance the night by one tick
+    pub fn tick(&mut self) {
+        if self.state.outcome.is_some() {
+            return;
+        }
+
+        self.state.tick += 1;
+        let tick = self.state.tick;
+        let ai = self.config.ai_levels;
+
+        self.state.move_cooldown_ticks = self.state.move_cooldown_ticks.saturating_sub(1);
+
+        if tick.is_multiple_of(TICKS_PER_HOUR) {
+            self.state.hour += 1;
+            if self.state.hour >= NIGHT_HOURS {
+                self.state.outcome = Some(NightOutcome::Survived);
+                return;
+            }
+        }
+
+        if self.state.power > 0 {
+            self.state.power -= self.state.usage as i64 * POWER_DRAIN_PER_USAGE;
+            if self.state.power <= 0 {
+                self.power_out();
+            }
+        }
+
+        if tick.is_multiple_of(BONNIE_MOVE_TICKS) && self.roll_ai(ai.bonnie) {
+            self.advance(Animatronic::Bonnie);
+        }
+
+        if tick.is_multiple_of(CHICA_MOVE_TICKS) && self.roll_ai(ai.chica) {
+            self.advance(Animatronic::Chica);
+        }
+
+        // Freddy only moves while the cameras are down
+        if tick.is_multiple_of(FREDDY_MOVE_TICKS) && !self.state.camera_open && self.roll_ai(ai.freddy) {
+            self.advance(Animatronic::Freddy);
+        }
+
+        if tick.is_multiple_of(FOXY_MOVE_TICKS) && self.foxy_run_at.is_none() {
+            if self.state.camera_open && self.state.current_camera == PIRATE_COVE {
+                // Looking at Pirate Cove holds Foxy back
+                self.state.foxy_stage = (self.state.foxy_stage - 1).max(0);
+            } else if self.roll_ai(ai.foxy) {
+                self.state.foxy_stage += 1;
+                if self.state.foxy_stage >= 4 {
+                    self.foxy_run();
+                }
+            }
+        }
+
+        if self.foxy_run_at == Some(tick) {
+            self.foxy_run_at = None;
+            self.resolve_foxy_run();
+        }
+
+        if tick.is_multiple_of(ATTACK_CHECK_TICKS) {
+            self.check_for_attacks();
+        }
+
+        if self.power_out_attack_at == Some(tick) && self.state.outcome.is_none() {
+            self.jumpscare(Animatronic::Freddy);
+        }
+    }
+
+    /// Run the night to the end, applying each intent before the tick it is scheduled for
+    pub fn run(&mut self, inputs: &[(u32, Intent)]) -> Result<(), String> {
+        let mut inputs = inputs.iter().peekable();
+
+        while self.state.outcome.is_none() {
+            while let Some((_, intent)) = inputs.next_if(|(tick, _)| *tick <= self.state.tick) {
+                self.apply(intent)?;
+            }
+            self.tick();
+        }
+
+        Ok(())
+    }
+
+    fn roll_ai(&mut self, level: i32) -> bool {
+        self.rng.gen_range(0.0..20.0) < level as f64
+    }
+
+    fn advance(&mut self, animatronic: Animatronic) {
+        let (path, position) = match animatronic {
+            Animatronic::Freddy => (FREDDY_PATH, &mut self.state.positions.freddy),
+            Animatronic::Bonnie => (BONNIE_PATH, &mut self.state.positions.bonnie),
+            Animatronic::Chica => (CHICA_PATH, &mut self.state.positions.chica),
+            Animatronic::Foxy => return,
+        };
+
+        if let Some(index) = path.iter().position(|p| p == position) {
+            if index + 1 < path.len() {
+                *position = path[index + 1];
+            }
+        }
+    }
+
+    fn foxy_run(&mut self) {
+        self.state.positions.foxy = LEFT_DOOR;
+        self.foxy_run_at = Some(self.state.tick + FOXY_RUN_TICKS);
+    }
+
+    fn resolve_foxy_run(&mut self) {
+        if self.state.positions.foxy != LEFT_DOOR {
+            return;
+        }
+
+        if self.state.left_door_closed {
+            // Foxy bangs on the door and drains power
+            self.state.power -= 5 * self.config.night as i64 * POWER_SCALE;
+            self.state.positions.foxy = PIRATE_COVE;
+            self.state.foxy_stage = 0;
+            if self.state.power <= 0 && !self.state.power_out {
+                self.power_out();
+            }
+        } else {
+            self.jumpscare(Animatronic::Foxy);
+        }
+    }
+
+    fn check_for_attacks(&mut self) {
+        let positions = self.state.positions.clone();
+
+        if positions.bonnie == LEFT_DOOR && !self.state.left_door_closed && self.rng.gen::<f64>() < 0.3 {
+            self.jumpscare(Animatronic::Bonnie);
+        }
+
+        if positions.chica == RIGHT_DOOR && !self.state.right_door_closed && self.rng.gen::<f64>() < 0.3 {
+            self.jumpscare(Animatronic::Chica);
+        }
+
+        if positions.freddy == RIGHT_DOOR
+            && !self.state.right_door_closed
+            && self.rng.gen::<f64>() < 0.3 + self.config.ai_levels.freddy as f64 * 0.02
+        {
+            self.jumpscare(Animatronic::Freddy);
+        }
+    }
+
+    fn power_out(&mut self) {
+        let state = &mut self.state;
+        state.power = 0;
+        state.power_out = true;
+        state.left_door_closed = false;
+        state.right_door_closed = false;
+        state.left_light_on = false;
+        state.right_light_on = false;
+
+        // Freddy comes after a delay
+        self.power_out_attack_at = Some(self.state.tick + self.rng.gen_range(POWER_OUT_ATTACK_TICKS));
+    }
+
+    fn jumpscare(&mut self, by: Animatronic) {
+        if self.state.outcome.is_none() {
+            self.state.outcome = Some(NightOutcome::Jumpscare { by });
+        }
+    }
+
+    fn update_usage(&mut self) {
+        let state = &mut self.state;
+        state.usage = 1
+            + state.left_door_closed as i32
+            + state.right_door_closed as i32
+            + state.left_light_on as i32
+            + state.right_light_on as i32
+            + state.camera_open as i32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDLE: AiLevels = AiLevels { freddy: 0, bonnie: 0, chica: 0, foxy: 0 };
+
+    fn idle_night(starting_power: i32) -> NightSimulation {
+        let mut config = NightConfig::new(1, Some(IDLE), false);
+        config.starting_power = starting_power;
+        NightSimulation::new(config, 7)
+    }
+
+    fn tick_n(sim: &mut NightSimulation, ticks: u32) {
+        for _ in 0..ticks {
+            sim.tick();
+        }
+    }
+
+    #[test]
+    fn same_seed_and_inputs_replay_the_same_night() {
+        let inputs = vec![
+            (20, Intent::ToggleCamera),
+            (25, Intent::SwitchCamera { camera: "1B".to_string() }),
+            (300, Intent::ToggleCamera),
+            (310, Intent::ToggleLeftDoor),
+            (900, Intent::ToggleLeftDoor),
+        ];
+
+        for seed in [1, 42, 9001] {
+            let config = NightConfig::new(5, None, false);
+            let mut first = NightSimulation::new(config, seed);
+            let mut second = NightSimulation::new(config, seed);
+            first.run(&inputs).unwrap();
+            second.run(&inputs).unwrap();
+
+            assert_eq!(first.outcome(), second.outcome());
+            assert_eq!(first.state().tick, second.state().tick);
+            assert_eq!(first.state().power, second.state().power);
+        }
+    }
+
+    #[test]
+    fn power_runs_out_after_the_base_drain() {
+        let mut sim = idle_night(10);
+        let expected = (10 * POWER_SCALE + POWER_DRAIN_PER_USAGE - 1) / POWER_DRAIN_PER_USAGE;
+
+        tick_n(&mut sim, expected as u32 - 1);
+        assert!(!sim.state().power_out);
+
+        sim.tick();
+        assert!(sim.state().power_out);
+        assert_eq!(sim.state().tick as i64, expected);
+        assert_eq!(sim.state().power, 0);
+    }
+
+    #[test]
+    fn closed_left_door_stops_foxy() {
+        let mut sim = idle_night(100);
+        sim.apply(&Intent::ToggleLeftDoor).unwrap();
+        sim.apply(&Intent::MoveAnimatronic { animatronic: Animatronic::Foxy }).unwrap();
+        tick_n(&mut sim, FOXY_RUN_TICKS);
+
+        assert_eq!(sim.outcome(), None);
+        assert_eq!(sim.state().positions.foxy, PIRATE_COVE);
+        assert_eq!(sim.state().foxy_stage, 0);
+    }
+
+    #[test]
+    fn open_left_door_lets_foxy_in() {
+        let mut sim = idle_night(100);
+        sim.apply(&Intent::MoveAnimatronic { animatronic: Animatronic::Foxy }).unwrap();
+        tick_n(&mut sim, FOXY_RUN_TICKS);
+
+        assert_eq!(sim.outcome(), Some(NightOutcome::Jumpscare { by: Animatronic::Foxy }));
+    }
+
+    #[test]
+    fn max_power_after_bounds_the_simulation() {
+        assert_eq!(max_power_after(100, 0), 100);
+        assert_eq!(max_power_after(10, 3600), 0);
+
+        // Only the base usage bar drains, so the night ends right at the bound
+        let mut idle = idle_night(100);
+        idle.run(&[]).unwrap();
+        assert_eq!(idle.outcome(), Some(NightOutcome::Survived));
+        assert_eq!(idle.power_percent(), max_power_after(100, idle.elapsed_seconds()));
+
+        let mut busy = idle_night(100);
+        busy.apply(&Intent::ToggleLeftDoor).unwrap();
+        busy.apply(&Intent::ToggleRightLight).unwrap();
+        while busy.outcome().is_none() {
+            busy.tick();
+            assert!(busy.power_percent() <= max_power_after(100, busy.elapsed_seconds()));
+        }
+    }
+}
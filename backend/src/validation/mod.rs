@@ -0,0 +1,147 @@
+// Request validation
+// Request models implement `Validate`, handlers take them through `ValidatedJson`
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    Json,
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+
+/// Messages per field name
+pub type FieldErrors = BTreeMap<String, Vec<String>>;
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// Collects field errors while a request is checked
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: FieldErrors,
+}
+
+impl Validator {
+    pub fn check(&mut self, ok: bool, field: &str, message: impl Into<String>) {
+        if !ok {
+            self.errors
+                .entry(field.to_string())
+                .or_default()
+                .push(message.into());
+        }
+    }
+
+    pub fn length(&mut self, value: &str, field: &str, min: usize, max: usize) {
+        let len = value.chars().count();
+        self.check(
+            (min..=max).contains(&len),
+            field,
+            format!("must be between {} and {} characters", min, max),
+        );
+    }
+
+    pub fn range<T: PartialOrd + std::fmt::Display>(&mut self, value: Option<T>, field: &str, min: T, max: T) {
+        if let Some(value) = value {
+            let ok = value >= min && value <= max;
+            self.check(ok, field, format!("must be between {} and {}", min, max));
+        }
+    }
+
+    pub fn username(&mut self, value: &str, field: &str) {
+        self.length(value, field, 3, 32);
+        self.check(
+            value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            field,
+            "may only contain letters, digits, '_' and '-'",
+        );
+    }
+
+    pub fn email(&mut self, value: &str, field: &str) {
+        let valid = value.len() <= 255
+            && !value.chars().any(char::is_whitespace)
+            && matches!(value.split_once('@'), Some((local, domain))
+                if !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && !domain.ends_with('.'));
+        self.check(valid, field, "must be a valid email address");
+    }
+
+    pub fn password(&mut self, value: &str, field: &str) {
+        self.length(value, field, 8, 128);
+        self.check(
+            value.chars().any(|c| c.is_alphabetic()) && value.chars().any(|c| c.is_ascii_digit()),
+            field,
+            "must contain at least one letter and one digit",
+        );
+    }
+
+    /// Tokens handed out by `generate_opaque_token`
+    pub fn token(&mut self, value: &str, field: &str) {
+        self.check(
+            value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit()),
+            field,
+            "is not a valid token",
+        );
+    }
+
+    /// A JSON array of short strings
+    pub fn string_list(&mut self, value: Option<&serde_json::Value>, field: &str, max_items: usize, max_len: usize) {
+        let Some(value) = value else {
+            return;
+        };
+
+        match value.as_array() {
+            Some(items) => {
+                self.check(
+                    items.len() <= max_items,
+                    field,
+                    format!("may contain at most {} items", max_items),
+                );
+                self.check(
+                    items
+                        .iter()
+                        .all(|item| item.as_str().map_or(false, |s| !s.is_empty() && s.len() <= max_len)),
+                    field,
+                    format!("items must be strings of at most {} characters", max_len),
+                );
+            }
+            None => self.check(false, field, "must be an array"),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), AppError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(self.errors))
+        }
+    }
+}
+
+/// `Json<T>` that also runs `T::validate`
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state)
+            .await
+            .map_err(|e| AppError::BadRequest(e.body_text()))?;
+
+        let mut v = Validator::default();
+        value.validate(&mut v);
+        v.finish()?;
+
+        Ok(ValidatedJson(value))
+    }
+}
@@ -0,0 +1,138 @@
+// Matchmaking socket - join and leave the ranked queue and get the match pushed when it is made.
+// Closing the socket leaves the queue
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::IntoResponse,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::{error_message, AUTH_TIMEOUT};
+use crate::{
+    error::AppError,
+    matchmaking,
+    models::*,
+    routes::auth::{decode_token, is_token_revoked},
+    services::MatchmakingService,
+    validation::{Validate, Validator},
+    AppState,
+};
+
+pub async fn matchmaking_ws_handler(
+    ws: WebSocketUpgrade,
+    Query(query): Query<WsConnectQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    // Ranked play needs an account, guest tokens are not accepted
+    let user_id = match query.token.as_deref() {
+        Some(token) => Some(authenticate(&state, token).await?),
+        None => None,
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, user_id, state)))
+}
+
+async fn authenticate(state: &AppState, token: &str) -> Result<Uuid, AppError> {
+    let claims = decode_token(token, &state.config.jwt_secret)?;
+    if is_token_revoked(&state.db, claims.jti).await? {
+        return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+    }
+
+    Ok(claims.sub)
+}
+
+async fn handle_socket(socket: WebSocket, user_id: Option<Uuid>, state: AppState) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let authenticated = match user_id {
+        Some(user_id) => Ok(user_id),
+        None => match tokio::time::timeout(AUTH_TIMEOUT, receiver.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => {
+                match serde_json::from_str::<MatchmakingClientMessage>(&text) {
+                    Ok(MatchmakingClientMessage::Auth { token }) => authenticate(&state, &token).await,
+                    _ => Err(AppError::Unauthorized("First message must be Auth".to_string())),
+                }
+            }
+            Ok(_) => Err(AppError::Unauthorized("Missing token".to_string())),
+            Err(_) => Err(AppError::Unauthorized("Authentication timed out".to_string())),
+        },
+    };
+
+    let user_id = match authenticated {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            let msg = MatchmakingServerMessage::Error {
+                message: error_message(e),
+            };
+            let _ = sender.send(Message::Text(encode(&msg))).await;
+            return;
+        }
+    };
+
+    // Replies and the match found by the queue go out through the same channel
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    let send_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sender.send(Message::Text(msg)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(msg)) = receiver.next().await {
+        let Message::Text(text) = msg else {
+            continue;
+        };
+        let Ok(client_msg) = serde_json::from_str::<MatchmakingClientMessage>(&text) else {
+            continue;
+        };
+
+        let reply = handle_client_message(&state, user_id, client_msg, &tx)
+            .await
+            .unwrap_or_else(|e| MatchmakingServerMessage::Error {
+                message: error_message(e),
+            });
+        if tx.send(encode(&reply)).is_err() {
+            break;
+        }
+    }
+
+    // Only the entry this socket queued, the player may have queued again elsewhere
+    matchmaking::dequeue_notified_by(user_id, &tx);
+    send_task.abort();
+}
+
+async fn handle_client_message(
+    state: &AppState,
+    user_id: Uuid,
+    msg: MatchmakingClientMessage,
+    tx: &mpsc::UnboundedSender<String>,
+) -> Result<MatchmakingServerMessage, AppError> {
+    match msg {
+        MatchmakingClientMessage::Auth { .. } => {
+            Err(AppError::BadRequest("Already authenticated".to_string()))
+        }
+        MatchmakingClientMessage::Join { role } => {
+            let req = JoinQueueRequest { role };
+            let mut v = Validator::default();
+            req.validate(&mut v);
+            v.finish()?;
+
+            let status = MatchmakingService::join(&state.db, user_id, &req.role, Some(tx.clone())).await?;
+            Ok(MatchmakingServerMessage::Queued { status })
+        }
+        MatchmakingClientMessage::Leave => {
+            matchmaking::dequeue(user_id);
+            Ok(MatchmakingServerMessage::Left)
+        }
+        MatchmakingClientMessage::Ping => Ok(MatchmakingServerMessage::Pong),
+    }
+}
+
+fn encode(msg: &MatchmakingServerMessage) -> String {
+    serde_json::to_string(msg).unwrap()
+}
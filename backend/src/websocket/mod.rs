@@ -1,19 +1,35 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
+        Path, Query, State,
     },
     response::IntoResponse,
 };
-use futures::{SinkExt, StreamExt};
+use chrono::Utc;
+use futures::{stream::SplitStream, SinkExt, StreamExt};
+use serde::Serialize;
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{error::AppError, models::*, AppState};
+use crate::{
+    error::AppError,
+    models::*,
+    routes::{
+        auth::{decode_token, is_token_revoked},
+        multiplayer::get_room_participants,
+    },
+    services::{generate_opaque_token, hash_token, RatingService},
+    simulation::{AiLevels, Intent, NightConfig, NightOutcome, NightSimulation, TICK_MS},
+    AppState,
+};
+
+pub mod matchmaking;
 
 // Store active game rooms
 lazy_static::lazy_static! {
@@ -21,81 +37,515 @@ lazy_static::lazy_static! {
 }
 
 pub struct GameRoom {
+    pub room_id: Uuid,
     pub room_code: String,
-    pub tx: broadcast::Sender<String>,
+    pub max_players: usize,
+    pub game_mode: String,
+    pub settings: Option<serde_json::Value>,
+    pub status: String,
+    pub ranked: bool,
+    pub channel: Arc<RoomChannel>,
     pub game_state: Option<serde_json::Value>,
-    pub players: Vec<ConnectedPlayer>,
+    pub simulation: Option<Arc<Mutex<NightSimulation>>>,
+    pub players: Vec<ConnectedPlayer>, // includes disconnected players whose slot is held
+}
+
+impl GameRoom {
+    fn from_db(room: &MultiplayerRoom) -> Self {
+        GameRoom {
+            room_id: room.id,
+            room_code: room.room_code.clone(),
+            max_players: room.max_players.max(0) as usize,
+            game_mode: room.game_mode.clone(),
+            settings: room.settings.clone(),
+            status: room.status.clone(),
+            ranked: room.ranked,
+            channel: Arc::new(RoomChannel::new()),
+            game_state: None,
+            simulation: None,
+            players: Vec::new(),
+        }
+    }
+
+    /// A versus game whose simulation was lost, e.g. across a restart, can not go on
+    fn interrupted(&self) -> bool {
+        self.game_mode == GAME_MODE_VERSUS && self.status == ROOM_PLAYING && self.simulation.is_none()
+    }
+
+    fn connected_count(&self) -> usize {
+        self.players.iter().filter(|p| p.connected).count()
+    }
+
+    /// The latest game state, versus rooms snapshot their simulation
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        match &self.simulation {
+            Some(simulation) => Some(simulation.lock().unwrap().snapshot()),
+            None => self.game_state.clone(),
+        }
+    }
 }
 
 pub struct ConnectedPlayer {
-    pub id: Uuid,
+    pub id: Uuid, // multiplayer_participants.id
     pub user_id: Option<Uuid>,
+    pub name: String, // username or guest name
     pub role: Option<String>,
     pub is_ready: bool,
+    pub direct_tx: mpsc::UnboundedSender<String>,
+    pub connection_id: Uuid, // the socket currently holding the slot
+    pub connected: bool,
+    pub reconnect_token_hash: String,
+}
+
+/// Broadcast channel of a room. Every broadcast gets the next sequence number and is kept
+/// in a short log, so a player who reconnects can be sent the events they missed
+pub struct RoomChannel {
+    tx: broadcast::Sender<String>,
+    log: Mutex<EventLog>,
 }
 
+#[derive(Default)]
+struct EventLog {
+    seq: u64,
+    events: VecDeque<LoggedEvent>,
+}
+
+struct LoggedEvent {
+    seq: u64,
+    game_state: bool, // superseded by the snapshot sent on reconnect in versus rooms
+    text: String,
+}
+
+#[derive(Serialize)]
+struct SequencedMessage<'a> {
+    seq: u64,
+    #[serde(flatten)]
+    msg: &'a ServerMessage,
+}
+
+impl RoomChannel {
+    fn new() -> Self {
+        let (tx, _) = broadcast::channel(100);
+        RoomChannel {
+            tx,
+            log: Mutex::new(EventLog::default()),
+        }
+    }
+
+    pub fn send(&self, msg: &ServerMessage) {
+        // The log stays locked while sending so events go out in sequence order
+        let mut log = self.log.lock().unwrap();
+        log.seq += 1;
+        let seq = log.seq;
+        let text = serde_json::to_string(&SequencedMessage { seq, msg }).unwrap();
+
+        if log.events.len() == EVENT_LOG_SIZE {
+            log.events.pop_front();
+        }
+        log.events.push_back(LoggedEvent {
+            seq,
+            game_state: matches!(msg, ServerMessage::GameState { .. }),
+            text: text.clone(),
+        });
+
+        let _ = self.tx.send(text);
+    }
+
+    /// Subscribe to live events. Also returns the logged events after `after_seq` and the
+    /// latest sequence number, taken together so nothing is missed or sent twice
+    fn subscribe(&self, after_seq: u64, skip_game_state: bool) -> (broadcast::Receiver<String>, Vec<String>, u64) {
+        let log = self.log.lock().unwrap();
+        let rx = self.tx.subscribe();
+        let missed = log
+            .events
+            .iter()
+            .filter(|e| e.seq > after_seq && !(skip_game_state && e.game_state))
+            .map(|e| e.text.clone())
+            .collect();
+
+        (rx, missed, log.seq)
+    }
+}
+
+/// Who a socket belongs to
+struct SocketIdentity {
+    participant: MultiplayerParticipant,
+    name: String,
+    reconnecting: bool,    // authenticated with a reconnect token
+    last_seq: Option<u64>, // last room event the client saw
+}
+
+/// How long an unauthenticated socket may wait before sending its `Auth` message
+pub(crate) const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A dropped player's slot is held this long for them to reconnect
+const RECONNECT_GRACE: Duration = Duration::from_secs(30);
+
+/// Room events kept for replay to reconnecting players
+const EVENT_LOG_SIZE: usize = 256;
+
+/// Versus mode snapshots are broadcast every this many ticks, and after every intent
+const SNAPSHOT_EVERY_TICKS: u32 = 5;
+
 pub async fn game_ws_handler(
     ws: WebSocketUpgrade,
     Path(room_code): Path<String>,
+    Query(query): Query<WsConnectQuery>,
     State(state): State<AppState>,
-) -> impl IntoResponse {
-    ws.on_upgrade(move |socket| handle_socket(socket, room_code, state))
+) -> Result<impl IntoResponse, AppError> {
+    // Only rooms created through the API can be joined
+    let room = sqlx::query_as!(
+        MultiplayerRoom,
+        "SELECT * FROM multiplayer_rooms WHERE room_code = $1",
+        room_code
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+
+    // Players can still come back to see how a game they dropped out of ended
+    if room.status == ROOM_FINISHED && query.reconnect_token.is_none() {
+        return Err(AppError::BadRequest("Game has already ended".to_string()));
+    }
+
+    // Credentials in the query string are checked before upgrading,
+    // otherwise the first message on the socket has to be `Auth`
+    let identity = if query.token.is_some()
+        || query.guest_token.is_some()
+        || query.reconnect_token.is_some()
+    {
+        Some(
+            identify(
+                &state,
+                &room,
+                query.token.as_deref(),
+                query.guest_token.as_deref(),
+                query.reconnect_token.as_deref(),
+                query.last_seq,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, room, identity, state)))
+}
+
+/// Resolve who a socket belongs to. A reconnect token resumes a held slot,
+/// otherwise a user JWT or a guest token is needed
+async fn identify(
+    state: &AppState,
+    room: &MultiplayerRoom,
+    token: Option<&str>,
+    guest_token: Option<&str>,
+    reconnect_token: Option<&str>,
+    last_seq: Option<u64>,
+) -> Result<SocketIdentity, AppError> {
+    let (participant, name, reconnecting) = match reconnect_token {
+        Some(reconnect_token) => {
+            let (participant, name) = reconnect(state, &room.room_code, reconnect_token).await?;
+            (participant, name, true)
+        }
+        None => {
+            let (participant, name) = authenticate(state, room.id, token, guest_token).await?;
+            (participant, name, false)
+        }
+    };
+
+    Ok(SocketIdentity {
+        participant,
+        name,
+        reconnecting,
+        last_seq,
+    })
+}
+
+/// Resolve the participant row a socket belongs to from a user JWT or a guest token
+async fn authenticate(
+    state: &AppState,
+    room_id: Uuid,
+    token: Option<&str>,
+    guest_token: Option<&str>,
+) -> Result<(MultiplayerParticipant, String), AppError> {
+    if let Some(token) = token {
+        let claims = decode_token(token, &state.config.jwt_secret)?;
+        if is_token_revoked(&state.db, claims.jti).await? {
+            return Err(AppError::Unauthorized("Token has been revoked".to_string()));
+        }
+
+        let participant = sqlx::query_as!(
+            MultiplayerParticipant,
+            "SELECT * FROM multiplayer_participants WHERE room_id = $1 AND user_id = $2",
+            room_id,
+            claims.sub
+        )
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("You have not joined this room".to_string()))?;
+
+        let user = sqlx::query!(
+            "SELECT username FROM users WHERE id = $1",
+            claims.sub
+        )
+        .fetch_one(&state.db)
+        .await?;
+
+        Ok((participant, user.username))
+    } else if let Some(guest_token) = guest_token {
+        let participant = sqlx::query_as!(
+            MultiplayerParticipant,
+            "SELECT * FROM multiplayer_participants WHERE room_id = $1 AND guest_token_hash = $2",
+            room_id,
+            hash_token(guest_token)
+        )
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::Forbidden("Invalid guest token".to_string()))?;
+
+        let name = participant
+            .guest_name
+            .clone()
+            .unwrap_or_else(|| "Guest".to_string());
+
+        Ok((participant, name))
+    } else {
+        Err(AppError::Unauthorized("Missing token".to_string()))
+    }
+}
+
+/// Resolve the slot a reconnect token was issued for. Tokens only live as long as the room
+/// is held in memory, and only the latest one issued to a player is valid
+async fn reconnect(
+    state: &AppState,
+    room_code: &str,
+    reconnect_token: &str,
+) -> Result<(MultiplayerParticipant, String), AppError> {
+    let token_hash = hash_token(reconnect_token);
+    let (player_id, name) = {
+        let rooms = GAME_ROOMS.read().await;
+        rooms
+            .get(room_code)
+            .and_then(|room| room.players.iter().find(|p| p.reconnect_token_hash == token_hash))
+            .map(|p| (p.id, p.name.clone()))
+            .ok_or_else(|| AppError::Unauthorized("Reconnect token is invalid or expired".to_string()))?
+    };
+
+    let participant = sqlx::query_as!(
+        MultiplayerParticipant,
+        "SELECT * FROM multiplayer_participants WHERE id = $1",
+        player_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok((participant, name))
 }
 
-async fn handle_socket(socket: WebSocket, room_code: String, state: AppState) {
+async fn handle_socket(
+    socket: WebSocket,
+    room: MultiplayerRoom,
+    identity: Option<SocketIdentity>,
+    state: AppState,
+) {
     let (mut sender, mut receiver) = socket.split();
-    let player_id = Uuid::new_v4();
 
-    // Get or create room broadcast channel
-    let tx = {
+    let identified = match identity {
+        Some(identity) => Ok(identity),
+        None => authenticate_first_message(&mut receiver, &state, &room).await,
+    };
+
+    let SocketIdentity {
+        participant,
+        name,
+        reconnecting,
+        last_seq,
+    } = match identified {
+        Ok(identity) => identity,
+        Err(e) => {
+            let msg = ServerMessage::Error {
+                message: error_message(e),
+            };
+            let _ = sender.send(Message::Text(encode(&msg))).await;
+            return;
+        }
+    };
+
+    let player_id = participant.id;
+    let room_code = room.room_code.clone();
+    let (direct_tx, mut direct_rx) = mpsc::unbounded_channel::<String>();
+    let connection_id = Uuid::new_v4();
+    let reconnect_token = generate_opaque_token();
+
+    // Join the in-memory room, creating it from the database row if needed,
+    // or take back a held slot
+    let joined = {
         let mut rooms = GAME_ROOMS.write().await;
-        if let Some(room) = rooms.get_mut(&room_code) {
-            room.players.push(ConnectedPlayer {
-                id: player_id,
-                user_id: None,
-                role: None,
-                is_ready: false,
-            });
-            room.tx.clone()
-        } else {
-            let (tx, _) = broadcast::channel(100);
-            rooms.insert(
-                room_code.clone(),
-                GameRoom {
-                    room_code: room_code.clone(),
-                    tx: tx.clone(),
-                    game_state: None,
-                    players: vec![ConnectedPlayer {
-                        id: player_id,
-                        user_id: None,
-                        role: None,
-                        is_ready: false,
-                    }],
-                },
-            );
-            tx
+        let game_room = rooms
+            .entry(room_code.clone())
+            .or_insert_with(|| GameRoom::from_db(&room));
+
+        // Nobody can finish an interrupted versus game, so it ends unrated
+        let interrupted = game_room.interrupted();
+        if interrupted {
+            game_room.status = ROOM_FINISHED.to_string();
         }
+
+        let slots_taken = game_room.players.len();
+        let resumed = match game_room.players.iter_mut().find(|p| p.id == player_id) {
+            // A reconnect token takes the slot over even if the old socket has not noticed it dropped
+            Some(player) if !player.connected || reconnecting => {
+                player.direct_tx = direct_tx.clone();
+                player.connection_id = connection_id;
+                player.connected = true;
+                player.reconnect_token_hash = hash_token(&reconnect_token);
+                Ok(true)
+            }
+            Some(_) => Err("Already connected to this room"),
+            None if slots_taken >= game_room.max_players => Err("Room is full"),
+            None => {
+                game_room.players.push(ConnectedPlayer {
+                    id: player_id,
+                    user_id: participant.user_id,
+                    name: name.clone(),
+                    role: participant.role.clone(),
+                    is_ready: participant.is_ready,
+                    direct_tx: direct_tx.clone(),
+                    connection_id,
+                    connected: true,
+                    reconnect_token_hash: hash_token(&reconnect_token),
+                });
+                Ok(false)
+            }
+        };
+
+        let joined = resumed.map(|resumed| {
+            // Without a position only the snapshot is sent
+            let after_seq = if resumed { last_seq.unwrap_or(u64::MAX) } else { u64::MAX };
+            let (rx, missed, seq) = game_room
+                .channel
+                .subscribe(after_seq, game_room.simulation.is_some());
+
+            (
+                game_room.channel.clone(),
+                rx,
+                resumed,
+                missed,
+                seq,
+                game_room.snapshot(),
+                game_room.connected_count(),
+            )
+        });
+
+        if game_room.players.is_empty() {
+            rooms.remove(&room_code);
+        }
+
+        joined.map(|joined| (joined, interrupted))
     };
 
-    let mut rx = tx.subscribe();
+    let (channel, mut rx, resumed, missed, seq, snapshot) = match joined {
+        Ok(((channel, rx, resumed, missed, seq, snapshot, connected), interrupted)) => {
+            if interrupted {
+                if let Err(e) = finish_room(&state, room.id).await {
+                    tracing::error!("Failed to finish interrupted room {}: {:?}", room_code, e);
+                }
+            }
+            if let Err(e) = set_player_count(&state, room.id, connected).await {
+                tracing::error!("Failed to update player count for room {}: {:?}", room_code, e);
+            }
+            (channel, rx, resumed, missed, seq, snapshot)
+        }
+        Err(message) => {
+            let msg = ServerMessage::Error {
+                message: message.to_string(),
+            };
+            let _ = sender.send(Message::Text(encode(&msg))).await;
+            return;
+        }
+    };
 
-    // Send task - forwards broadcast messages to this client
+    // Catch the client up before any live event: its session, the current room and game
+    // state, then the events it missed while away
+    let mut catch_up = vec![encode(&ServerMessage::Session {
+        participant_id: player_id,
+        reconnect_token,
+        seq,
+        grace_seconds: RECONNECT_GRACE.as_secs(),
+        resumed,
+    })];
+    if resumed {
+        match room_state(&state, room.id).await {
+            Ok(msg) => catch_up.push(encode(&msg)),
+            Err(e) => tracing::error!("Failed to load room state for {}: {:?}", room_code, e),
+        }
+    }
+    if let Some(snapshot) = snapshot {
+        catch_up.push(encode(&ServerMessage::GameState { state: snapshot }));
+    }
+    catch_up.extend(missed);
+
+    for msg in catch_up {
+        let _ = sender.send(Message::Text(msg)).await;
+    }
+
+    // Send task - forwards broadcast and direct messages to this client
     let send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                Ok(msg) = rx.recv() => msg,
+                Some(msg) = direct_rx.recv() => msg,
+                else => break,
+            };
             if sender.send(Message::Text(msg)).await.is_err() {
                 break;
             }
         }
     });
 
+    if resumed {
+        channel.send(&ServerMessage::PlayerReconnected {
+            participant_id: player_id,
+        });
+    } else {
+        let joined_msg = ServerMessage::PlayerJoined {
+            participant: ParticipantInfo {
+                id: player_id,
+                username: participant.user_id.map(|_| name.clone()),
+                guest_name: participant.guest_name.clone(),
+                role: participant.role.clone(),
+                is_ready: participant.is_ready,
+            },
+        };
+        channel.send(&joined_msg);
+
+        if let Err(e) = broadcast_room_state(&state, room.id, &channel).await {
+            tracing::error!("Failed to broadcast room state for {}: {:?}", room_code, e);
+        }
+    }
+
     // Receive task - handles incoming messages from this client
     let room_code_clone = room_code.clone();
-    let tx_clone = tx.clone();
+    let channel_clone = channel.clone();
+    let state_clone = state.clone();
     let recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
                 if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                    handle_client_message(&room_code_clone, player_id, client_msg, &tx_clone).await;
+                    if let Err(e) = handle_client_message(
+                        &state_clone,
+                        &room_code_clone,
+                        player_id,
+                        client_msg,
+                        &channel_clone,
+                    )
+                    .await
+                    {
+                        let msg = ServerMessage::Error {
+                            message: error_message(e),
+                        };
+                        let _ = direct_tx.send(encode(&msg));
+                    }
                 }
             }
         }
@@ -107,61 +557,303 @@ async fn handle_socket(socket: WebSocket, room_code: String, state: AppState) {
         _ = recv_task => {},
     }
 
-    // Clean up - remove player from room
-    {
+    // Hold the slot for the grace period, unless another socket has taken it over already
+    let disconnected = {
         let mut rooms = GAME_ROOMS.write().await;
-        if let Some(room) = rooms.get_mut(&room_code) {
-            room.players.retain(|p| p.id != player_id);
+        rooms.get_mut(&room_code).and_then(|room| {
+            let player = room
+                .players
+                .iter_mut()
+                .find(|p| p.id == player_id && p.connection_id == connection_id)?;
+            player.connected = false;
 
-            // Broadcast player left
-            let msg = ServerMessage::PlayerLeft {
-                participant_id: player_id,
+            Some((room.room_id, room.connected_count()))
+        })
+    };
+
+    if let Some((room_id, connected)) = disconnected {
+        channel.send(&ServerMessage::PlayerDisconnected {
+            participant_id: player_id,
+            grace_seconds: RECONNECT_GRACE.as_secs(),
+        });
+
+        if let Err(e) = set_player_count(&state, room_id, connected).await {
+            tracing::error!("Failed to update player count for room {}: {:?}", room_code, e);
+        }
+
+        spawn_grace_expiry(state, room_code, player_id, connection_id);
+    }
+}
+
+/// Give up a dropped player's slot if they have not reconnected within the grace period
+fn spawn_grace_expiry(state: AppState, room_code: String, player_id: Uuid, connection_id: Uuid) {
+    tokio::spawn(async move {
+        tokio::time::sleep(RECONNECT_GRACE).await;
+
+        let left = {
+            let mut rooms = GAME_ROOMS.write().await;
+            let Some(room) = rooms.get_mut(&room_code) else {
+                return;
             };
-            let _ = tx.send(serde_json::to_string(&msg).unwrap());
+
+            // Back in the meantime, the slot belongs to a newer socket
+            let expired = room
+                .players
+                .iter()
+                .any(|p| p.id == player_id && p.connection_id == connection_id && !p.connected);
+            if !expired {
+                return;
+            }
+
+            room.players.retain(|p| p.id != player_id);
+            room.channel.send(&ServerMessage::PlayerLeft {
+                participant_id: player_id,
+            });
+
+            let left = (
+                room.room_id,
+                room.status.clone(),
+                room.players.len(),
+                room.connected_count(),
+            );
 
             // Remove room if empty
             if room.players.is_empty() {
                 rooms.remove(&room_code);
             }
+
+            left
+        };
+
+        let (room_id, status, remaining, connected) = left;
+        if let Err(e) = persist_leave(&state, room_id, player_id, &status, remaining, connected).await {
+            tracing::error!("Failed to persist leave for room {}: {:?}", room_code, e);
         }
+    });
+}
+
+/// Wait for the `Auth` message on a socket that connected without credentials
+async fn authenticate_first_message(
+    receiver: &mut SplitStream<WebSocket>,
+    state: &AppState,
+    room: &MultiplayerRoom,
+) -> Result<SocketIdentity, AppError> {
+    let first = tokio::time::timeout(AUTH_TIMEOUT, receiver.next())
+        .await
+        .map_err(|_| AppError::Unauthorized("Authentication timed out".to_string()))?;
+
+    match first {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMessage>(&text) {
+            Ok(ClientMessage::Auth {
+                token,
+                guest_token,
+                reconnect_token,
+                last_seq,
+            }) => {
+                identify(
+                    state,
+                    room,
+                    token.as_deref(),
+                    guest_token.as_deref(),
+                    reconnect_token.as_deref(),
+                    last_seq,
+                )
+                .await
+            }
+            _ => Err(AppError::Unauthorized(
+                "First message must be Auth".to_string(),
+            )),
+        },
+        _ => Err(AppError::Unauthorized("Missing token".to_string())),
     }
 }
 
 async fn handle_client_message(
+    state: &AppState,
     room_code: &str,
     player_id: Uuid,
     msg: ClientMessage,
-    tx: &broadcast::Sender<String>,
-) {
+    channel: &Arc<RoomChannel>,
+) -> Result<(), AppError> {
     match msg {
+        ClientMessage::Auth { .. } => {
+            return Err(AppError::BadRequest("Already authenticated".to_string()));
+        }
         ClientMessage::Ping => {
-            let response = ServerMessage::Pong;
-            let _ = tx.send(serde_json::to_string(&response).unwrap());
+            send_to_player(room_code, player_id, &ServerMessage::Pong).await;
         }
         ClientMessage::Ready => {
-            let mut rooms = GAME_ROOMS.write().await;
-            if let Some(room) = rooms.get_mut(room_code) {
+            let (room_id, ranked, start, simulation) = {
+                let mut rooms = GAME_ROOMS.write().await;
+                let room = rooms
+                    .get_mut(room_code)
+                    .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+
+                if room.status != ROOM_WAITING {
+                    return Err(AppError::BadRequest("Game already started".to_string()));
+                }
+
+                // Check if all players ready
+                let all_ready = room.players.iter().all(|p| p.id == player_id || p.is_ready);
+                let start = all_ready && room.players.len() >= 2;
+
+                // The simulation and ranked results need one player on each side
+                if start && room.game_mode == GAME_MODE_VERSUS {
+                    let count = |role: &str| {
+                        room.players
+                            .iter()
+                            .filter(|p| p.role.as_deref() == Some(role))
+                            .count()
+                    };
+                    if count(ROOM_ROLE_GUARD) != 1 || count(ROOM_ROLE_ANIMATRONIC) != 1 {
+                        return Err(AppError::BadRequest(
+                            "Versus games need exactly one guard and one animatronic".to_string(),
+                        ));
+                    }
+                }
+
                 if let Some(player) = room.players.iter_mut().find(|p| p.id == player_id) {
                     player.is_ready = true;
                 }
+                let mut simulation = None;
+                if start {
+                    room.status = ROOM_PLAYING.to_string();
 
-                // Check if all players ready
-                let all_ready = room.players.iter().all(|p| p.is_ready);
-                if all_ready && room.players.len() >= 2 {
-                    let msg = ServerMessage::GameStart;
-                    let _ = tx.send(serde_json::to_string(&msg).unwrap());
+                    if room.game_mode == GAME_MODE_VERSUS {
+                        let config = night_config(room.settings.as_ref());
+                        let sim = Arc::new(Mutex::new(NightSimulation::new(config, rand::random())));
+                        room.simulation = Some(sim.clone());
+                        simulation = Some(sim);
+                    }
+                }
+
+                (room.room_id, room.ranked, start, simulation)
+            };
+
+            sqlx::query!(
+                "UPDATE multiplayer_participants SET is_ready = true WHERE id = $1",
+                player_id
+            )
+            .execute(&state.db)
+            .await?;
+
+            broadcast_room_state(state, room_id, channel).await?;
+
+            if start {
+                sqlx::query!(
+                    "UPDATE multiplayer_rooms SET status = $1, started_at = $2 WHERE id = $3",
+                    ROOM_PLAYING,
+                    Utc::now(),
+                    room_id
+                )
+                .execute(&state.db)
+                .await?;
+
+                channel.send(&ServerMessage::GameStart);
+
+                if let Some(simulation) = simulation {
+                    spawn_simulation(
+                        state.clone(),
+                        room_code.to_string(),
+                        room_id,
+                        ranked,
+                        simulation,
+                        channel.clone(),
+                    );
                 }
             }
         }
         ClientMessage::RoleSelect { role } => {
-            let mut rooms = GAME_ROOMS.write().await;
-            if let Some(room) = rooms.get_mut(room_code) {
+            if !ROOM_ROLES.contains(&role.as_str()) {
+                return Err(AppError::BadRequest(format!(
+                    "Invalid role. Valid roles: {:?}",
+                    ROOM_ROLES
+                )));
+            }
+
+            let room_id = {
+                let mut rooms = GAME_ROOMS.write().await;
+                let room = rooms
+                    .get_mut(room_code)
+                    .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+
+                if room.status != ROOM_WAITING {
+                    return Err(AppError::BadRequest("Game already started".to_string()));
+                }
+                if room.ranked {
+                    return Err(AppError::BadRequest(
+                        "Roles are assigned by matchmaking in ranked games".to_string(),
+                    ));
+                }
+
+                if room
+                    .players
+                    .iter()
+                    .any(|p| p.id != player_id && p.role.as_deref() == Some(role.as_str()))
+                {
+                    return Err(AppError::Conflict("Role already taken".to_string()));
+                }
+
                 if let Some(player) = room.players.iter_mut().find(|p| p.id == player_id) {
-                    player.role = Some(role);
+                    player.role = Some(role.clone());
                 }
-            }
+
+                room.room_id
+            };
+
+            sqlx::query!(
+                "UPDATE multiplayer_participants SET role = $1 WHERE id = $2",
+                role,
+                player_id
+            )
+            .execute(&state.db)
+            .await?;
+
+            broadcast_room_state(state, room_id, channel).await?;
         }
         ClientMessage::GameAction { action } => {
+            ensure_status(room_code, ROOM_PLAYING).await?;
+
+            let (simulation, role) = {
+                let rooms = GAME_ROOMS.read().await;
+                let room = rooms
+                    .get(room_code)
+                    .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+                if room.interrupted() {
+                    return Err(AppError::BadRequest(
+                        "This versus game was interrupted and can not continue".to_string(),
+                    ));
+                }
+                let role = room
+                    .players
+                    .iter()
+                    .find(|p| p.id == player_id)
+                    .and_then(|p| p.role.clone());
+
+                (room.simulation.clone(), role)
+            };
+
+            // Versus rooms only accept intents, the server decides what happens
+            if let Some(simulation) = simulation {
+                let intent = Intent::from_action(&action).map_err(AppError::BadRequest)?;
+
+                if role.as_deref() != Some(intent.role()) {
+                    return Err(AppError::Forbidden(format!(
+                        "Only the {} can do that",
+                        intent.role()
+                    )));
+                }
+
+                let snapshot = {
+                    let mut sim = simulation.lock().unwrap();
+                    sim.apply(&intent).map_err(AppError::BadRequest)?;
+                    sim.snapshot()
+                };
+
+                channel.send(&ServerMessage::GameState { state: snapshot });
+                return Ok(());
+            }
+
             // Broadcast action to all players
             let msg = ServerMessage::GameState {
                 state: serde_json::json!({
@@ -169,16 +861,280 @@ async fn handle_client_message(
                     "from": player_id.to_string()
                 }),
             };
-            let _ = tx.send(serde_json::to_string(&msg).unwrap());
+            channel.send(&msg);
+        }
+        ClientMessage::GameOver { result } => {
+            let room_id = {
+                let mut rooms = GAME_ROOMS.write().await;
+                let room = rooms
+                    .get_mut(room_code)
+                    .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+
+                if room.status != ROOM_PLAYING {
+                    return Err(AppError::BadRequest("Game is not in progress".to_string()));
+                }
+                if room.game_mode == GAME_MODE_VERSUS {
+                    return Err(AppError::BadRequest(
+                        "The server decides when a versus game ends".to_string(),
+                    ));
+                }
+                room.status = ROOM_FINISHED.to_string();
+
+                room.room_id
+            };
+
+            finish_room(state, room_id).await?;
+
+            channel.send(&ServerMessage::GameEnd { result });
         }
         ClientMessage::Chat { message } => {
-            let msg = ServerMessage::Chat {
-                from: player_id.to_string(),
-                message,
+            let from = {
+                let rooms = GAME_ROOMS.read().await;
+                rooms
+                    .get(room_code)
+                    .and_then(|room| room.players.iter().find(|p| p.id == player_id))
+                    .map(|p| p.name.clone())
+                    .ok_or_else(|| AppError::NotFound("Player not found".to_string()))?
             };
-            let _ = tx.send(serde_json::to_string(&msg).unwrap());
+
+            let msg = ServerMessage::Chat { from, message };
+            channel.send(&msg);
         }
     }
+
+    Ok(())
 }
 
-// Need to add lazy_static to Cargo.toml
+/// Night settings for a versus room: `night`, `easy_mode` and optional custom `ai_levels`
+fn night_config(settings: Option<&serde_json::Value>) -> NightConfig {
+    let setting = |key: &str| settings.and_then(|s| s.get(key));
+
+    let night = setting("night")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(1)
+        .clamp(1, 7) as i32;
+    let easy_mode = setting("easy_mode")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let ai_levels = setting("ai_levels")
+        .and_then(|v| serde_json::from_value::<AiLevels>(v.clone()).ok())
+        .map(AiLevels::clamped);
+
+    NightConfig::new(night, ai_levels, easy_mode)
+}
+
+/// Drive a versus room's simulation on a fixed tick until the night ends or the room closes.
+/// Ranked rooms are rated when the night ends, a room everyone left stays unrated
+fn spawn_simulation(
+    state: AppState,
+    room_code: String,
+    room_id: Uuid,
+    ranked: bool,
+    simulation: Arc<Mutex<NightSimulation>>,
+    channel: Arc<RoomChannel>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(TICK_MS));
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            // The room dropped its handle, everyone left
+            if Arc::strong_count(&simulation) == 1 {
+                break;
+            }
+
+            let (snapshot, outcome, seed) = {
+                let mut sim = simulation.lock().unwrap();
+                sim.tick();
+                let snapshot = (sim.outcome().is_some()
+                    || sim.state().tick.is_multiple_of(SNAPSHOT_EVERY_TICKS))
+                    .then(|| sim.snapshot());
+                (snapshot, sim.outcome(), sim.seed())
+            };
+
+            if let Some(snapshot) = &snapshot {
+                channel.send(&ServerMessage::GameState {
+                    state: snapshot.clone(),
+                });
+            }
+
+            let Some(outcome) = outcome else {
+                continue;
+            };
+
+            if let Some(room) = GAME_ROOMS.write().await.get_mut(&room_code) {
+                room.status = ROOM_FINISHED.to_string();
+            }
+
+            if let Err(e) = finish_room(&state, room_id).await {
+                tracing::error!("Failed to finish room {}: {:?}", room_code, e);
+            }
+
+            let ranked_match = if ranked {
+                let winner_role = match outcome {
+                    NightOutcome::Survived => ROOM_ROLE_GUARD,
+                    NightOutcome::Jumpscare { .. } => ROOM_ROLE_ANIMATRONIC,
+                };
+                RatingService::record_result(&state.db, room_id, winner_role)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!("Failed to rate ranked room {}: {:?}", room_code, e);
+                        None
+                    })
+            } else {
+                None
+            };
+
+            let result = serde_json::json!({
+                "outcome": outcome,
+                "seed": seed,
+                "state": snapshot,
+                "ranked_match": ranked_match,
+            });
+            channel.send(&ServerMessage::GameEnd { result });
+            break;
+        }
+    });
+}
+
+async fn ensure_status(room_code: &str, status: &str) -> Result<(), AppError> {
+    let rooms = GAME_ROOMS.read().await;
+    let room = rooms
+        .get(room_code)
+        .ok_or_else(|| AppError::NotFound("Room not found".to_string()))?;
+
+    if room.status != status {
+        return Err(AppError::BadRequest(format!("Room is {}", room.status)));
+    }
+
+    Ok(())
+}
+
+async fn send_to_player(room_code: &str, player_id: Uuid, msg: &ServerMessage) {
+    let rooms = GAME_ROOMS.read().await;
+    if let Some(player) = rooms
+        .get(room_code)
+        .and_then(|room| room.players.iter().find(|p| p.id == player_id))
+    {
+        let _ = player.direct_tx.send(encode(msg));
+    }
+}
+
+async fn broadcast_room_state(
+    state: &AppState,
+    room_id: Uuid,
+    channel: &RoomChannel,
+) -> Result<(), AppError> {
+    let msg = room_state(state, room_id).await?;
+    channel.send(&msg);
+
+    Ok(())
+}
+
+async fn room_state(state: &AppState, room_id: Uuid) -> Result<ServerMessage, AppError> {
+    let room = sqlx::query_as!(
+        MultiplayerRoom,
+        "SELECT * FROM multiplayer_rooms WHERE id = $1",
+        room_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let participants = get_room_participants(state, room_id).await?;
+
+    Ok(ServerMessage::RoomState {
+        room: RoomResponse { room, participants },
+    })
+}
+
+async fn set_player_count(state: &AppState, room_id: Uuid, count: usize) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE multiplayer_rooms SET current_players = $1 WHERE id = $2",
+        count as i32,
+        room_id
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+/// Finish the versus rooms left playing by a previous run, their simulations are gone.
+/// Call before accepting connections, the results stay unrated
+pub async fn finish_interrupted_rooms(db: &PgPool) -> anyhow::Result<u64> {
+    let result = sqlx::query!(
+        "UPDATE multiplayer_rooms SET status = $1, ended_at = $2 WHERE status = $3 AND game_mode = $4",
+        ROOM_FINISHED,
+        Utc::now(),
+        ROOM_PLAYING,
+        GAME_MODE_VERSUS
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn finish_room(state: &AppState, room_id: Uuid) -> Result<(), AppError> {
+    sqlx::query!(
+        "UPDATE multiplayer_rooms SET status = $1, ended_at = $2 WHERE id = $3",
+        ROOM_FINISHED,
+        Utc::now(),
+        room_id
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+async fn persist_leave(
+    state: &AppState,
+    room_id: Uuid,
+    player_id: Uuid,
+    status: &str,
+    remaining: usize,
+    connected: usize,
+) -> Result<(), AppError> {
+    set_player_count(state, room_id, connected).await?;
+
+    if status == ROOM_WAITING {
+        // Players have to ready up again when they come back
+        sqlx::query!(
+            "UPDATE multiplayer_participants SET is_ready = false WHERE id = $1",
+            player_id
+        )
+        .execute(&state.db)
+        .await?;
+    } else if status == ROOM_PLAYING && remaining == 0 {
+        finish_room(state, room_id).await?;
+    }
+
+    Ok(())
+}
+
+fn encode(msg: &ServerMessage) -> String {
+    serde_json::to_string(msg).unwrap()
+}
+
+pub(crate) fn error_message(err: AppError) -> String {
+    match err {
+        AppError::NotFound(msg)
+        | AppError::BadRequest(msg)
+        | AppError::Unauthorized(msg)
+        | AppError::Forbidden(msg)
+        | AppError::Conflict(msg)
+        | AppError::Internal(msg) => msg,
+        AppError::Database(e) => {
+            tracing::error!("Database error: {:?}", e);
+            "Database error".to_string()
+        }
+        AppError::Validation(fields) => fields
+            .into_iter()
+            .map(|(field, messages)| format!("{} {}", field, messages.join(", ")))
+            .collect::<Vec<_>>()
+            .join("; "),
+    }
+}